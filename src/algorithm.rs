@@ -0,0 +1,244 @@
+// Common interface over the SSP algorithms, so callers can pick one at runtime.
+
+use crate::block_list::BlockListKind;
+use crate::bmssp::BmsspParams;
+use crate::graph::Graph;
+use crate::query_context::QueryContext;
+use crate::shortest_path_tree::{MultiSourceTree, ShortestPathTree};
+use crate::{bmssp, dijkstra};
+use anyhow::Result;
+use std::fmt;
+use std::str::FromStr;
+
+pub trait ShortestPathAlgorithm {
+    fn name(&self) -> &'static str;
+
+    /// Distances from `source` to every node, `f64::INFINITY` when unreachable.
+    fn distances(&self, graph: &Graph, source: usize) -> Vec<f64>;
+
+    /// Distances plus the predecessor of every reached node.
+    fn tree(&self, graph: &Graph, source: usize) -> ShortestPathTree;
+
+    /// Point-to-point query. Only the distance to (and path to) `target` is final in the result.
+    fn tree_to(&self, graph: &Graph, source: usize, target: usize) -> ShortestPathTree;
+
+    /// Distance to, and owner of, every node's nearest `(source, initial_offset)`.
+    fn multi_source(&self, graph: &Graph, sources: &[(usize, f64)]) -> MultiSourceTree;
+
+    /// Nodes closer than `max_cost` to `source`, as `(node, distance)` by increasing distance.
+    /// Nothing at or beyond the bound is searched.
+    fn within(&self, graph: &Graph, source: usize, max_cost: f64) -> Vec<(usize, f64)>;
+
+    /// `tree` (or `tree_to` with a `target`) on `ctx`'s buffers, which are reused by the next
+    /// query instead of allocated again.
+    fn tree_with<'a>(
+        &self,
+        ctx: &'a mut QueryContext,
+        graph: &Graph,
+        source: usize,
+        target: Option<usize>,
+    ) -> &'a ShortestPathTree;
+
+    /// `within` on `ctx`'s buffers: the nodes it would return are those labelled below `max_cost`
+    /// in the returned tree. Other labels, if any, are upper bounds.
+    fn within_with<'a>(
+        &self,
+        ctx: &'a mut QueryContext,
+        graph: &Graph,
+        source: usize,
+        max_cost: f64,
+    ) -> &'a ShortestPathTree;
+}
+
+pub struct Dijkstra;
+
+impl ShortestPathAlgorithm for Dijkstra {
+    fn name(&self) -> &'static str {
+        "dijkstra"
+    }
+
+    fn distances(&self, graph: &Graph, source: usize) -> Vec<f64> {
+        dijkstra::dijkstra_all(graph, source)
+    }
+
+    fn tree(&self, graph: &Graph, source: usize) -> ShortestPathTree {
+        dijkstra::dijkstra_tree(graph, source)
+    }
+
+    fn tree_to(&self, graph: &Graph, source: usize, target: usize) -> ShortestPathTree {
+        dijkstra::dijkstra_to(graph, source, target)
+    }
+
+    fn multi_source(&self, graph: &Graph, sources: &[(usize, f64)]) -> MultiSourceTree {
+        dijkstra::dijkstra_multi(graph, sources)
+    }
+
+    fn within(&self, graph: &Graph, source: usize, max_cost: f64) -> Vec<(usize, f64)> {
+        dijkstra::dijkstra_within(graph, source, max_cost)
+    }
+
+    fn tree_with<'a>(
+        &self,
+        ctx: &'a mut QueryContext,
+        graph: &Graph,
+        source: usize,
+        target: Option<usize>,
+    ) -> &'a ShortestPathTree {
+        dijkstra::dijkstra_with(ctx, graph, source, target)
+    }
+
+    fn within_with<'a>(
+        &self,
+        ctx: &'a mut QueryContext,
+        graph: &Graph,
+        source: usize,
+        max_cost: f64,
+    ) -> &'a ShortestPathTree {
+        dijkstra::dijkstra_within_with(ctx, graph, source, max_cost)
+    }
+}
+
+/// BMSSP on the chosen block list backend and parameters (`Bmssp::default()` for the usual ones).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Bmssp {
+    pub block_list: BlockListKind,
+    // Set through `with_params`, which checks them.
+    params: Option<BmsspParams>,
+}
+
+impl Bmssp {
+    /// BMSSP on `block_list`, with the paper's parameters.
+    pub fn new(block_list: BlockListKind) -> Self {
+        Self {
+            block_list,
+            params: None,
+        }
+    }
+
+    /// Fixed k, t and l instead of the paper's values for each graph. Fails unless they cover a
+    /// graph of `num_nodes` nodes (see `BmsspParams::check`). Queries on a graph they can't cover
+    /// panic, so call `check` before reusing it on another graph.
+    pub fn with_params(self, params: BmsspParams, num_nodes: usize) -> Result<Self> {
+        params.check(num_nodes)?;
+        Ok(Self {
+            params: Some(params),
+            ..self
+        })
+    }
+
+    pub fn params(&self) -> Option<BmsspParams> {
+        self.params
+    }
+
+    /// Fails if the parameters set with `with_params` don't cover `graph`.
+    pub fn check(&self, graph: &Graph) -> Result<()> {
+        match self.params {
+            Some(params) => params.check(graph.num_nodes()),
+            None => Ok(()),
+        }
+    }
+
+    fn search(
+        &self,
+        graph: &Graph,
+        sources: &[(usize, f64)],
+        target: Option<usize>,
+        max_cost: f64,
+    ) -> ShortestPathTree {
+        bmssp::bmssp_search(graph, sources, target, max_cost, self)
+    }
+}
+
+impl ShortestPathAlgorithm for Bmssp {
+    fn name(&self) -> &'static str {
+        "bmssp"
+    }
+
+    fn distances(&self, graph: &Graph, source: usize) -> Vec<f64> {
+        self.tree(graph, source).dist
+    }
+
+    fn tree(&self, graph: &Graph, source: usize) -> ShortestPathTree {
+        self.search(graph, &[(source, 0.0)], None, f64::INFINITY)
+    }
+
+    fn tree_to(&self, graph: &Graph, source: usize, target: usize) -> ShortestPathTree {
+        self.search(graph, &[(source, 0.0)], Some(target), f64::INFINITY)
+    }
+
+    fn multi_source(&self, graph: &Graph, sources: &[(usize, f64)]) -> MultiSourceTree {
+        MultiSourceTree::new(self.search(graph, sources, None, f64::INFINITY))
+    }
+
+    fn within(&self, graph: &Graph, source: usize, max_cost: f64) -> Vec<(usize, f64)> {
+        if max_cost <= 0.0 {
+            return Vec::new();
+        }
+        let tree = self.search(graph, &[(source, 0.0)], None, max_cost);
+        bmssp::settled_below(tree, max_cost)
+    }
+
+    fn tree_with<'a>(
+        &self,
+        ctx: &'a mut QueryContext,
+        graph: &Graph,
+        source: usize,
+        target: Option<usize>,
+    ) -> &'a ShortestPathTree {
+        bmssp::bmssp_search_with(ctx, graph, &[(source, 0.0)], target, f64::INFINITY, self);
+        ctx.tree()
+    }
+
+    fn within_with<'a>(
+        &self,
+        ctx: &'a mut QueryContext,
+        graph: &Graph,
+        source: usize,
+        max_cost: f64,
+    ) -> &'a ShortestPathTree {
+        bmssp::bmssp_search_with(ctx, graph, &[(source, 0.0)], None, max_cost, self);
+        ctx.tree()
+    }
+}
+
+/// Algorithm selector for the command line (`--algorithm bmssp|dijkstra`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    Bmssp,
+    Dijkstra,
+}
+
+impl Algorithm {
+    pub fn solver(self) -> Box<dyn ShortestPathAlgorithm + Send + Sync> {
+        self.solver_with(Bmssp::default())
+    }
+
+    /// `solver`, with `bmssp` as the BMSSP configuration.
+    pub fn solver_with(self, bmssp: Bmssp) -> Box<dyn ShortestPathAlgorithm + Send + Sync> {
+        match self {
+            Algorithm::Bmssp => Box::new(bmssp),
+            Algorithm::Dijkstra => Box::new(Dijkstra),
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bmssp" => Ok(Algorithm::Bmssp),
+            "dijkstra" => Ok(Algorithm::Dijkstra),
+            _ => Err(format!(
+                "Algorithm not found for input string: {}, possible options are: (\"bmssp\", \"dijkstra\")",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.solver().name())
+    }
+}
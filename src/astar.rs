@@ -0,0 +1,98 @@
+// A*: Dijkstra ordered by the cost so far plus a lower bound on the cost still to go, so the search
+// heads for the target instead of growing evenly in every direction.
+
+use crate::graph::Graph;
+use crate::query_context::State;
+use crate::shortest_path_tree::{Predecessor, ShortestPathTree};
+use std::collections::BinaryHeap;
+
+/// Point-to-point A* from `start` to `target`. `heuristic(v)` must be a consistent lower bound on
+/// the cost from `v` to `target` (`h(u) <= w(u, v) + h(v)`), e.g. `OsmGraph::heuristic_to`; with
+/// `|_| 0.0` this is Dijkstra. Only the distance to (and path to) `target` is final in the returned
+/// tree.
+pub fn astar(
+    graph: &Graph,
+    start: usize,
+    target: usize,
+    heuristic: impl Fn(usize) -> f64,
+) -> ShortestPathTree {
+    let mut tree = ShortestPathTree::new(graph.num_nodes(), start);
+    // Keyed by the cost so far plus the heuristic. With a consistent heuristic a node's label is
+    // final when it is first popped, so later entries for it are stale.
+    let mut settled = vec![false; graph.num_nodes()];
+    let mut heap = BinaryHeap::new();
+    heap.push(State {
+        cost: heuristic(start),
+        node_id: start,
+    });
+
+    while let Some(State { node_id, .. }) = heap.pop() {
+        if settled[node_id] {
+            continue;
+        }
+        settled[node_id] = true;
+        let cost = tree.dist[node_id];
+        if node_id == target {
+            break;
+        }
+        for (edge_idx, next, w) in graph.edges(node_id) {
+            let next_cost = cost + w;
+            if !settled[next] && next_cost < tree.dist[next] {
+                tree.set(next, next_cost, Predecessor { node_id, edge_idx });
+                heap.push(State {
+                    cost: next_cost + heuristic(next),
+                    node_id: next,
+                });
+            }
+        }
+    }
+    tree
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dijkstra::dijkstra_all;
+    use crate::test_util::Rng;
+
+    #[test]
+    fn matches_dijkstra_on_a_grid() {
+        // 20x20 grid with unit coordinates, jittered weights no shorter than the straight line.
+        let side = 20;
+        let idx = |x: usize, y: usize| y * side + x;
+        let mut edges = Vec::new();
+        let mut rng = Rng(5);
+        let mut jitter = || 1.0 + rng.unit();
+        for y in 0..side {
+            for x in 0..side {
+                if x + 1 < side {
+                    edges.push((idx(x, y), idx(x + 1, y), jitter()));
+                    edges.push((idx(x + 1, y), idx(x, y), jitter()));
+                }
+                if y + 1 < side {
+                    edges.push((idx(x, y), idx(x, y + 1), jitter()));
+                    edges.push((idx(x, y + 1), idx(x, y), jitter()));
+                }
+            }
+        }
+        let graph = Graph::from_edges(side * side, &edges);
+        let start = idx(2, 3);
+        let dist = dijkstra_all(&graph, start);
+        for target in [start, idx(17, 15), idx(0, 19), idx(19, 0)] {
+            let (tx, ty) = ((target % side) as f64, (target / side) as f64);
+            let euclidean = |v: usize| {
+                let (x, y) = ((v % side) as f64, (v / side) as f64);
+                ((x - tx).powi(2) + (y - ty).powi(2)).sqrt()
+            };
+            let tree = astar(&graph, start, target, euclidean);
+            assert_eq!(tree.dist[target], dist[target], "target {}", target);
+            // Goal direction leaves part of the grid unlabelled.
+            let labelled = tree.dist.iter().filter(|d| d.is_finite()).count();
+            assert!(labelled < side * side, "target {}", target);
+
+            let path = tree.edges_to(target).unwrap();
+            let length: f64 = path.iter().map(|p| graph.edge(p.edge_idx).1).sum();
+            assert_eq!(length, dist[target]);
+        }
+    }
+}
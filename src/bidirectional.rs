@@ -0,0 +1,253 @@
+// Bidirectional point-to-point searches: one search forward from the source, one backward from the
+// target over the reversed graph, and the shortest path through the best meeting point.
+
+use crate::algorithm::Bmssp;
+use crate::bmssp;
+use crate::graph::{Graph, ReverseGraph};
+use crate::query_context::{QueryContext, State};
+use crate::shortest_path_tree::{Predecessor, ShortestPathTree};
+use std::collections::BinaryHeap;
+
+/// Best way found so far to join the two searches: forward tree to `from`, the edge `edge_idx`
+/// (None when the trees meet at a node), then the backward tree from its head.
+#[derive(Copy, Clone)]
+struct Meeting {
+    cost: f64,
+    from: usize,
+    edge_idx: Option<usize>,
+}
+
+/// Turns the forward tree into the result: the backward tree's path from the meeting point to the
+/// target is appended with original edge ids, so only the distance to (and path to) the target is
+/// final, as for a unidirectional point-to-point query.
+fn join(
+    graph: &Graph,
+    reverse: &ReverseGraph,
+    mut forward: ShortestPathTree,
+    backward: &ShortestPathTree,
+    meeting: Option<Meeting>,
+) -> ShortestPathTree {
+    let Some(Meeting { from, edge_idx, .. }) = meeting else {
+        return forward;
+    };
+    let mut cur = from;
+    let mut cost = forward.dist[cur];
+    let hop = |cur: usize, cost: &mut f64, edge_idx: usize, forward: &mut ShortestPathTree| {
+        let (head, weight) = graph.edge(edge_idx);
+        *cost += weight;
+        forward.dist[head] = *cost;
+        forward.pred[head] = Some(Predecessor {
+            node_id: cur,
+            edge_idx,
+        });
+        head
+    };
+    if let Some(edge_idx) = edge_idx {
+        cur = hop(cur, &mut cost, edge_idx, &mut forward);
+    }
+    while let Some(Predecessor { edge_idx, .. }) = backward.pred[cur] {
+        cur = hop(cur, &mut cost, reverse.forward_edge[edge_idx], &mut forward);
+    }
+    forward
+}
+
+/// Bidirectional Dijkstra from `start` to `target`. Alternates between the search with the cheaper
+/// queue head and stops once the two heads together cost at least the best path through a meeting
+/// point seen so far, since no path through unsettled nodes can beat it.
+pub fn dijkstra_bidirectional(
+    graph: &Graph,
+    reverse: &ReverseGraph,
+    start: usize,
+    target: usize,
+) -> ShortestPathTree {
+    let n = graph.num_nodes();
+    let mut trees = [
+        ShortestPathTree::new(n, start),
+        ShortestPathTree::new(n, target),
+    ];
+    let mut heaps = [BinaryHeap::new(), BinaryHeap::new()];
+    heaps[0].push(State {
+        cost: 0.0,
+        node_id: start,
+    });
+    heaps[1].push(State {
+        cost: 0.0,
+        node_id: target,
+    });
+    let mut best = (start == target).then_some(Meeting {
+        cost: 0.0,
+        from: start,
+        edge_idx: None,
+    });
+
+    loop {
+        let heads = [heaps[0].peek(), heaps[1].peek()].map(|h| h.map_or(f64::INFINITY, |s| s.cost));
+        let best_cost = best.map_or(f64::INFINITY, |m| m.cost);
+        if heads[0] + heads[1] >= best_cost || heads[0].is_infinite() || heads[1].is_infinite() {
+            break;
+        }
+        let side = if heads[0] <= heads[1] { 0 } else { 1 };
+        let State { cost, node_id } = heaps[side].pop().expect("head is finite");
+        if cost > trees[side].dist[node_id] {
+            continue;
+        }
+        let edges = if side == 0 { graph } else { &reverse.graph };
+        for (edge_idx, next, w) in edges.edges(node_id) {
+            let next_cost = cost + w;
+            if next_cost < trees[side].dist[next] {
+                trees[side].set(next, next_cost, Predecessor { node_id, edge_idx });
+                heaps[side].push(State {
+                    cost: next_cost,
+                    node_id: next,
+                });
+            }
+            let through = next_cost + trees[1 - side].dist[next];
+            if through < best.map_or(f64::INFINITY, |m| m.cost) {
+                best = Some(if side == 0 {
+                    Meeting {
+                        cost: through,
+                        from: node_id,
+                        edge_idx: Some(edge_idx),
+                    }
+                } else {
+                    Meeting {
+                        cost: through,
+                        from: next,
+                        edge_idx: Some(reverse.forward_edge[edge_idx]),
+                    }
+                });
+            }
+        }
+    }
+    let [forward, backward] = trees;
+    join(graph, reverse, forward, &backward, best)
+}
+
+/// Experimental bidirectional BMSSP, with `config`'s block list and parameters. BMSSP has no queue to alternate on, so both sides run bounded
+/// searches (`B = radius`) with a radius that doubles until it is large enough. Every node closer
+/// than the radius is complete on its side, and if the shortest path costs L < 2 * radius, it has
+/// an edge `u -> v` with `u` complete forward and `v` complete backward. So once the cheapest such
+/// edge costs less than 2 * radius, it is on a shortest path. Each round restarts both searches
+/// from scratch, on buffers kept from the last round, and only looks at the nodes they labelled.
+pub fn bmssp_bidirectional(
+    graph: &Graph,
+    reverse: &ReverseGraph,
+    start: usize,
+    target: usize,
+    config: &Bmssp,
+) -> ShortestPathTree {
+    let n = graph.num_nodes();
+    if start == target {
+        return ShortestPathTree::new(n, start);
+    }
+    let mut radius = graph
+        .neighbors(start)
+        .map(|(_, w)| w)
+        .filter(|&w| w > 0.0)
+        .fold(f64::INFINITY, f64::min);
+    if !radius.is_finite() {
+        radius = 1.0;
+    }
+    let mut forward = QueryContext::new();
+    let mut backward = QueryContext::new();
+    loop {
+        bmssp::bmssp_search_with(&mut forward, graph, &[(start, 0.0)], None, radius, config);
+        bmssp::bmssp_search_with(
+            &mut backward,
+            &reverse.graph,
+            &[(target, 0.0)],
+            None,
+            radius,
+            config,
+        );
+        let (forward_dist, backward_dist) = (&forward.tree().dist, &backward.tree().dist);
+        let mut best: Option<Meeting> = None;
+        for &u in forward.touched() {
+            if forward_dist[u] >= radius {
+                continue;
+            }
+            for (edge_idx, v, w) in graph.edges(u) {
+                if backward_dist[v] < radius {
+                    let through = forward_dist[u] + w + backward_dist[v];
+                    if through < best.map_or(f64::INFINITY, |m| m.cost) {
+                        best = Some(Meeting {
+                            cost: through,
+                            from: u,
+                            edge_idx: Some(edge_idx),
+                        });
+                    }
+                }
+            }
+        }
+        if best.is_some_and(|m| m.cost < 2.0 * radius) {
+            return join(graph, reverse, forward.into_tree(), backward.tree(), best);
+        }
+        // A side with nothing left past the radius has settled everything it can reach.
+        let exhausted = |ctx: &QueryContext| {
+            ctx.touched()
+                .iter()
+                .all(|&node_id| ctx.tree().dist[node_id] < radius)
+        };
+        if best.is_none() && (exhausted(&forward) || exhausted(&backward)) {
+            return forward.into_tree();
+        }
+        radius *= 2.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_list::BlockListKind;
+    use crate::bmssp::BmsspParams;
+    use crate::dijkstra::dijkstra_all;
+    use crate::test_util::{random_edges, Rng};
+
+    #[test]
+    fn matches_dijkstra() {
+        let n = 300;
+        // A node nothing leads to.
+        let mut edges = random_edges(&mut Rng(21), n, 3);
+        edges.retain(|&(u, v, _)| u != 7 && v != 7);
+        let graph = Graph::from_edges(n, &edges);
+        let reverse = graph.reverse();
+        let paper = Bmssp::new(BlockListKind::Paper)
+            .with_params(BmsspParams { k: 2, t: 1, l: 8 }, n)
+            .unwrap();
+
+        for start in [0, 7, 150] {
+            let dist = dijkstra_all(&graph, start);
+            for (target, &expected) in dist.iter().enumerate() {
+                for tree in [
+                    dijkstra_bidirectional(&graph, &reverse, start, target),
+                    bmssp_bidirectional(&graph, &reverse, start, target, &Bmssp::default()),
+                    // Another block list, with small steps per level.
+                    bmssp_bidirectional(&graph, &reverse, start, target, &paper),
+                ] {
+                    assert_eq!(tree.dist[target].is_finite(), expected.is_finite());
+                    if expected.is_infinite() {
+                        assert_eq!(tree.path_to(target), None);
+                        continue;
+                    }
+                    // The joined path adds up to the distance, whichever way its halves were summed.
+                    let length: f64 = tree
+                        .edges_to(target)
+                        .unwrap()
+                        .iter()
+                        .map(|p| graph.edge(p.edge_idx).1)
+                        .sum();
+                    assert_eq!(length, tree.dist[target]);
+                    assert!(
+                        (length - expected).abs() < 1e-9,
+                        "{} -> {}: {} vs {}",
+                        start,
+                        target,
+                        length,
+                        expected
+                    );
+                    assert_eq!(tree.path_to(target).unwrap()[0], start);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+// Little-endian read/write helpers for the on-disk graph formats.
+
+use std::io::{self, Read, Write};
+
+pub(crate) fn write_u8<W: Write>(w: &mut W, v: u8) -> io::Result<()> {
+    w.write_all(&[v])
+}
+
+pub(crate) fn read_u8<R: Read>(r: &mut R) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+pub(crate) fn write_u32<W: Write>(w: &mut W, v: u32) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+pub(crate) fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+pub(crate) fn write_u64<W: Write>(w: &mut W, v: u64) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+pub(crate) fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+pub(crate) fn write_f64<W: Write>(w: &mut W, v: f64) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+pub(crate) fn read_f64<R: Read>(r: &mut R) -> io::Result<f64> {
+    Ok(f64::from_bits(read_u64(r)?))
+}
+
+/// Length-prefixed slice of indices, each stored as a u64.
+pub(crate) fn write_usizes<W: Write>(w: &mut W, values: &[usize]) -> io::Result<()> {
+    write_u64(w, values.len() as u64)?;
+    for &v in values {
+        write_u64(w, v as u64)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_usizes<R: Read>(r: &mut R) -> io::Result<Vec<usize>> {
+    let len = read_u64(r)? as usize;
+    (0..len).map(|_| read_u64(r).map(|v| v as usize)).collect()
+}
+
+/// Length-prefixed slice of f64s.
+pub(crate) fn write_f64s<W: Write>(w: &mut W, values: &[f64]) -> io::Result<()> {
+    write_u64(w, values.len() as u64)?;
+    for &v in values {
+        write_f64(w, v)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_f64s<R: Read>(r: &mut R) -> io::Result<Vec<f64>> {
+    let len = read_u64(r)? as usize;
+    (0..len).map(|_| read_f64(r)).collect()
+}
@@ -1,18 +1,33 @@
 /*
-Block data structure proposed in https://arxiv.org/pdf/2504.17033v1.
+Block data structure proposed in https://arxiv.org/pdf/2504.17033v1 (Lemma 3.3).
 
 Parameterized by M, and an upper bound B over all values (assuming values are floats) in the block.
 Supported operations are Add (Insert), BatchPreprend, and Pull
 
 Insert(k, v): Update the value if the key exists in a block by first deleting it, then adding it. Adding
 the key-value pair means finding the right block (O(log(N/M)), and then inserting it in the block while potentially
-updating its upper bound.
+updating its upper bound. A full block is split around its median in O(M), which amortizes to O(1) per insert.
 Batch-Prepend(L): Adds L elements to D0, assuming they are all currently cheaper than all other elements in the data structure.
+      More than M elements are split around medians into blocks of at most ceil(M/2), in O(L log(L/M)).
 Pull: Pulls the least M costliest elements and returns the minimum upper bound after the pull.
-      This traverses the block lists D0 and D1 in order and pulls the number of elements needed.
+      This gathers the front blocks of D0 and D1 until each has at least M elements, and selects the M
+      cheapest of those, in time linear in the elements gathered.
+
+Medians come from the `medians` crate in linear time, and the M-th cheapest is selected around them in place, also in O(len).
+D1 is ordered by (cost, node id), so ties never straddle two blocks, and D0's blocks are numbered in list order.
+Either way a node's block is found by binary search, and its index within the block is kept next to its cost, so
+deleting it is O(log(N/M)) however many costs tie.
+Blocks are kept in VecDeques rather than a search tree; splitting one shifts the block headers after it.
+The node Vecs of emptied blocks and the scratch buffers are kept for reuse, so a list that is reset and filled
+again stops allocating once it has grown, except for the median search (see median_key).
 */
 
+// M and B follow the paper's notation.
+#![allow(non_snake_case)]
+
+use crate::block_list::BlockListOps;
 use hashbrown::HashMap;
+use medians::{Median, Medians};
 use std::cmp::Ordering;
 use std::collections::VecDeque;
 
@@ -23,71 +38,229 @@ pub type Cost = f64;
 struct Block {
     nodes: Vec<(NodeId, Cost)>,
     upper_bound: Cost,
+    // In D1, the node id that goes with `upper_bound`: the block holds the nodes ordered at or
+    // after the previous block's (upper_bound, tiebreak) and before its own. In D0, the block's
+    // place in the list, counting down as blocks are prepended.
+    tiebreak: usize,
     capacity: usize,
 }
 
 #[derive(Debug)]
-enum BlockAdditionResult<'a> {
-    Success(&'a Block),
-    SplitBlocks(Block, Block),
+enum BlockAdditionResult {
+    Success,
+    // The block kept its cheaper half; this is the dearer one.
+    Split(Block),
 }
 
-#[derive(Debug)]
-enum BlockRemovalResult {
-    FullRemoval(Vec<NodeId>, Cost),
-    PartialRemoval(Vec<NodeId>, Cost),
-    NoElementsLeft(Cost),
+pub struct PullResult(pub Vec<NodeId>, pub Cost);
+
+// Nodes moved or compared by splits, selections and scans, for the complexity tests. Only counted
+// in test builds; otherwise it is empty and `add` does nothing.
+#[derive(Debug, Default)]
+struct Work(#[cfg(test)] usize);
+
+impl Work {
+    #[inline(always)]
+    fn add(&mut self, nodes: usize) {
+        #[cfg(test)]
+        {
+            self.0 += nodes;
+        }
+        #[cfg(not(test))]
+        let _ = nodes;
+    }
 }
 
-pub struct PullResult(pub Vec<NodeId>, pub Cost);
+// By cost, then by node id, so that no two nodes compare equal.
+fn by_key(a: &(NodeId, Cost), b: &(NodeId, Cost)) -> Ordering {
+    a.1.total_cmp(&b.1).then(a.0.cmp(&b.0))
+}
+
+// Lower median of `nodes` by (cost, node id), in O(len). `median_by` builds a Vec of references to
+// the nodes on every call and takes no buffer to reuse, so this is the one place where the block list
+// still allocates per operation: O(len) bytes over a whole selection, as the rounds halve. A reused
+// QueryContext keeps the rest of its buffers.
+fn median_key(nodes: &[(NodeId, Cost)]) -> (NodeId, Cost) {
+    match nodes
+        .median_by(&mut by_key)
+        .expect("median of an empty block")
+    {
+        Medians::Odd(median) => *median,
+        // Two nodes come back in their original order.
+        Medians::Even((a, b)) => {
+            if by_key(a, b).is_le() {
+                *a
+            } else {
+                *b
+            }
+        }
+    }
+}
+
+// Reorders `nodes` so that the k-th (from 0) by (cost, node id) is at index k, the ones before it
+// are cheaper and the ones after it dearer, in O(len): partitions around the median and keeps the
+// side k is on.
+fn select_nth(nodes: &mut [(NodeId, Cost)], k: usize) {
+    let (mut lo, mut hi) = (0, nodes.len());
+    while hi - lo > 1 {
+        let median = median_key(&nodes[lo..hi]);
+        let mut num_left = lo;
+        for i in lo..hi {
+            if by_key(&nodes[i], &median).is_lt() {
+                nodes.swap(num_left, i);
+                num_left += 1;
+            }
+        }
+        // The median goes right after the cheaper nodes.
+        let median_idx = (num_left..hi)
+            .find(|&i| by_key(&nodes[i], &median).is_eq())
+            .expect("the median is one of the nodes");
+        nodes.swap(num_left, median_idx);
+        match k.cmp(&num_left) {
+            Ordering::Less => hi = num_left,
+            Ordering::Equal => return,
+            Ordering::Greater => lo = num_left + 1,
+        }
+    }
+}
+
+// Moves the dearer half of `nodes` (rounded down) by (cost, node id) to `right_nodes`, in O(len).
+fn split_at_median(nodes: &mut Vec<(NodeId, Cost)>, right_nodes: &mut Vec<(NodeId, Cost)>) {
+    let num_left = nodes.len().div_ceil(2);
+    select_nth(nodes, num_left - 1);
+    right_nodes.extend(nodes.drain(num_left..));
+}
+
+// Cost of the k-th cheapest (from 0) of `nodes`, in O(len). Reorders them.
+fn select_cost(nodes: &mut [(NodeId, Cost)], k: usize) -> Cost {
+    select_nth(nodes, k);
+    nodes[k].1
+}
+
+// Orders `nodes` around medians so that each `max_size` chunk of it costs at most as much as the
+// chunks after it, in O(len log(len / max_size)). Returns the nodes moved.
+fn partition_into_chunks(nodes: &mut [(NodeId, Cost)], max_size: usize) -> usize {
+    let num_chunks = nodes.len().div_ceil(max_size);
+    if num_chunks <= 1 {
+        return 0;
+    }
+    let split = num_chunks.div_ceil(2) * max_size;
+    select_nth(nodes, split - 1);
+    let num_nodes = nodes.len();
+    let (left, right) = nodes.split_at_mut(split);
+    num_nodes + partition_into_chunks(left, max_size) + partition_into_chunks(right, max_size)
+}
+
+// An empty node Vec, from `spare` if there is one.
+fn spare_nodes(spare: &mut Vec<Vec<(NodeId, Cost)>>) -> Vec<(NodeId, Cost)> {
+    spare.pop().unwrap_or_default()
+}
+
+// Keeps an emptied block's node Vec for reuse.
+fn recycle(spare: &mut Vec<Vec<(NodeId, Cost)>>, mut nodes: Vec<(NodeId, Cost)>) {
+    nodes.clear();
+    spare.push(nodes);
+}
 
 impl Block {
     fn new(M: usize, upper_bound: Cost) -> Self {
         Block::from_existing(M, upper_bound, Vec::with_capacity(M))
     }
 
+    // A last D1 block, ordered after every node with a cost up to `upper_bound`.
     fn from_existing(M: usize, upper_bound: Cost, nodes: Vec<(NodeId, Cost)>) -> Self {
         Self {
             nodes,
             upper_bound,
+            tiebreak: NodeId::MAX,
             capacity: M,
         }
     }
 
-    fn add(&mut self, node_id: NodeId, cost: Cost) -> BlockAdditionResult {
-        if self.nodes.len() < self.capacity {
-            self.nodes.push((node_id, cost));
-            BlockAdditionResult::Success(self)
+    // Whether a D1 node is ordered after this block's nodes.
+    fn is_before(&self, node: &(NodeId, Cost)) -> bool {
+        by_key(&(self.tiebreak, self.upper_bound), node).is_le()
+    }
+
+    // Splitting a full block moves its dearer half into a Vec from `spare`.
+    fn add(
+        &mut self,
+        node_id: NodeId,
+        cost: Cost,
+        spare: &mut Vec<Vec<(NodeId, Cost)>>,
+    ) -> BlockAdditionResult {
+        self.nodes.push((node_id, cost));
+        if self.nodes.len() <= self.capacity {
+            BlockAdditionResult::Success
         } else {
-            // We must split the block in two.
-            // NOTE: Optimized, this could be O(M), but we just sort the block for simplicity and split it.
-            let mut left_nodes = self.nodes.clone();
-            left_nodes.push((node_id, cost));
-            left_nodes.sort_by(|&a, &b| a.1.partial_cmp(&b.1).unwrap());
-            // Take M/2 nodes in the left and M/2 in the right.
-            let right_nodes: Vec<_> = left_nodes.drain((self.capacity / 2 + 1)..).collect();
-            BlockAdditionResult::SplitBlocks(
-                Block::from_existing(self.capacity, right_nodes[0].1, left_nodes),
-                Block::from_existing(self.capacity, self.upper_bound, right_nodes),
-            )
+            // We must split the block in two, around the median.
+            let mut right_nodes = spare_nodes(spare);
+            split_at_median(&mut self.nodes, &mut right_nodes);
+            let (tiebreak, upper_bound) = *right_nodes
+                .iter()
+                .min_by(|a, b| by_key(a, b))
+                .expect("the dearer half of a full block");
+            let right_block = Block {
+                nodes: right_nodes,
+                upper_bound: self.upper_bound,
+                tiebreak: self.tiebreak,
+                capacity: self.capacity,
+            };
+            self.upper_bound = upper_bound;
+            self.tiebreak = tiebreak;
+            BlockAdditionResult::Split(right_block)
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 enum BlockLocation {
-    Prepend(Cost),
+    // In the D0 block with this tiebreak.
+    Prepend(Cost, usize),
     Insert(Cost),
+    // In the batch being prepended, at the index stored next to it in cost_map.
+    Pending(Cost),
+}
+
+impl BlockLocation {
+    fn cost(self) -> Cost {
+        match self {
+            BlockLocation::Prepend(cost, _)
+            | BlockLocation::Insert(cost)
+            | BlockLocation::Pending(cost) => cost,
+        }
+    }
+}
+
+// Records where each of `block`'s nodes now is, after its nodes moved. `in_d0` says which list
+// the block is in.
+fn index_block(cost_map: &mut HashMap<NodeId, (BlockLocation, usize)>, block: &Block, in_d0: bool) {
+    for (i, &(node_id, cost)) in block.nodes.iter().enumerate() {
+        let location = if in_d0 {
+            BlockLocation::Prepend(cost, block.tiebreak)
+        } else {
+            BlockLocation::Insert(cost)
+        };
+        cost_map.insert(node_id, (location, i));
+    }
 }
 
 #[derive(Debug)]
 pub struct BlockList {
     M: usize,
     B: Cost,
+    // D0: each block's upper bound is its most expensive node.
     prepend_blocks: VecDeque<Block>,
+    // D1: each block holds the nodes between the previous block's upper bound and its own, by
+    // (cost, node id).
     insert_blocks: VecDeque<Block>,
-    cost_map: HashMap<NodeId, BlockLocation>, // map of node ids to existing locations.
-    len: usize,
+    cost_map: HashMap<NodeId, (BlockLocation, usize)>, // map of node ids to existing locations and their index in the block.
+    // Node Vecs of emptied blocks, for new blocks to reuse.
+    spare: Vec<Vec<(NodeId, Cost)>>,
+    // Scratch: the deduplicated batch being prepended, and the nodes a pull selects from.
+    batch: Vec<(NodeId, Cost)>,
+    candidates: Vec<(NodeId, Cost)>,
+    work: Work,
 }
 
 impl BlockList {
@@ -98,10 +271,31 @@ impl BlockList {
             prepend_blocks: VecDeque::new(),
             insert_blocks: vec![Block::new(M, B)].into(),
             cost_map: HashMap::new(),
-            len: 0,
+            spare: Vec::new(),
+            batch: Vec::new(),
+            candidates: Vec::new(),
+            work: Work::default(),
         }
     }
 
+    /// Empties the list and sets new parameters, keeping the allocations for reuse.
+    pub fn reset(&mut self, M: usize, B: Cost) {
+        self.M = M;
+        self.B = B;
+        for block in self
+            .prepend_blocks
+            .drain(..)
+            .chain(self.insert_blocks.drain(..))
+        {
+            recycle(&mut self.spare, block.nodes);
+        }
+        let nodes = spare_nodes(&mut self.spare);
+        self.insert_blocks
+            .push_back(Block::from_existing(M, B, nodes));
+        self.cost_map.clear();
+        self.work = Work::default();
+    }
+
     pub fn len(&self) -> usize {
         self.cost_map.len()
     }
@@ -110,78 +304,62 @@ impl BlockList {
         self.cost_map.is_empty()
     }
 
-    fn remove_from_prepend_list(&mut self, node_id: NodeId, cost: Cost) {
-        let prepend_idx = self
-            .prepend_blocks
-            .partition_point(|block| block.upper_bound < cost);
-        // This means it's not in the prepend block!
-        assert_ne!(prepend_idx, self.prepend_blocks.len());
-
-        // Now remove the node and its old cost from the prepend block.
-        let block = &mut self.prepend_blocks[prepend_idx];
-        if let Some(i) = block.nodes.iter().position(|&n| n.0 == node_id) {
-            block.nodes.swap_remove(i);
-        }
-
-        // If the vec was empty, we need to remove the block and "move" its upper bound to the previous block (if it exists).
-        if block.nodes.is_empty() {
-            if prepend_idx > 0 {
-                self.prepend_blocks[prepend_idx - 1].upper_bound = block.upper_bound;
+    fn remove(&mut self, node_id: NodeId, location: BlockLocation, index: usize) {
+        let (blocks, block_idx, in_d0) = match location {
+            BlockLocation::Prepend(_, tiebreak) => {
+                let blocks = &mut self.prepend_blocks;
+                let block_idx = blocks.partition_point(|block| block.tiebreak < tiebreak);
+                (blocks, block_idx, true)
             }
-            self.prepend_blocks.remove(prepend_idx);
+            BlockLocation::Insert(cost) => {
+                let blocks = &mut self.insert_blocks;
+                let block_idx = blocks.partition_point(|block| block.is_before(&(node_id, cost)));
+                (blocks, block_idx, false)
+            }
+            BlockLocation::Pending(_) => unreachable!("pending nodes aren't in a block yet"),
+        };
+        debug_assert_eq!(blocks[block_idx].nodes[index].0, node_id);
+
+        // Now remove the node and its old cost from the block.
+        let block = &mut blocks[block_idx];
+        block.nodes.swap_remove(index);
+        if let Some(&(moved_node_id, _)) = block.nodes.get(index) {
+            self.cost_map.get_mut(&moved_node_id).unwrap().1 = index;
         }
-    }
 
-    fn remove_from_insert_list(&mut self, node_id: NodeId, cost: Cost) {
-        let insert_idx = self
-            .insert_blocks
-            .partition_point(|block| block.upper_bound < cost);
-        // This means it's not in the insert block!
-        assert_ne!(insert_idx, self.insert_blocks.len());
-
-        // Now remove the node and its old cost from the prepend block.
-        let block = &mut self.insert_blocks[insert_idx];
-        if let Some(i) = block.nodes.iter().position(|&n| n.0 == node_id) {
-            block.nodes.swap_remove(i);
-        }
-        // If the vec was empty, we need to remove the block and "move" its upper bound to the previous block if it exists.
+        // If the vec was empty, we need to remove the block. In D1 its range goes to the previous block (if it exists), by
+        // "moving" its upper bound there, and the last block stays, so there is always a block to insert into.
         if block.nodes.is_empty() {
-            if insert_idx > 0 {
-                self.insert_blocks[insert_idx - 1].upper_bound = block.upper_bound;
+            if !in_d0 && block_idx > 0 {
+                blocks[block_idx - 1].upper_bound = blocks[block_idx].upper_bound;
+                blocks[block_idx - 1].tiebreak = blocks[block_idx].tiebreak;
             }
-            if insert_idx != self.insert_blocks.len() - 1 && self.insert_blocks.len() != 1 {
-                self.insert_blocks.remove(insert_idx);
+            if in_d0 || block_idx != blocks.len() - 1 {
+                let block = blocks.remove(block_idx).expect("found above");
+                recycle(&mut self.spare, block.nodes);
             }
         }
     }
 
     fn update(&mut self, node_id: NodeId, new_cost: Cost) -> bool {
         match self.cost_map.get(&node_id) {
-            Some(BlockLocation::Prepend(prepend_cost)) => {
-                if new_cost < *prepend_cost {
-                    self.remove_from_prepend_list(node_id, *prepend_cost);
-                    true
-                } else {
-                    false
-                }
-            }
-            Some(BlockLocation::Insert(insert_cost)) => {
-                if new_cost < *insert_cost {
-                    self.remove_from_insert_list(node_id, *insert_cost);
+            Some(&(location, index)) => {
+                if new_cost < location.cost() {
+                    self.remove(node_id, location, index);
                     true
                 } else {
                     false
                 }
             }
-            _ => true, // Node isn't here, so we can add this node to the cost map.
+            None => true, // Node isn't here, so we can add this node to the cost map.
         }
     }
 
     pub fn insert(&mut self, node_id: NodeId, cost: Cost) {
-        // it should *never* be >= B for D1 inserts.
+        // it should *never* be > B for D1 inserts.
         assert!(
             cost <= self.B,
-            "inserted cost {} >= B {} into D1",
+            "inserted cost {} > B {} into D1",
             cost,
             self.B
         );
@@ -191,85 +369,84 @@ impl BlockList {
             // Cost is not less, return early!
             return;
         }
-        // Now insert it.
-        self.cost_map.insert(node_id, BlockLocation::Insert(cost));
         // First find the block we want to insert into using the partition search.
         let i = self
             .insert_blocks
-            .partition_point(|block| block.upper_bound < cost);
+            .partition_point(|block| block.is_before(&(node_id, cost)));
         let block_to_add_to = &mut self.insert_blocks[i];
-        if let BlockAdditionResult::SplitBlocks(left_block, right_block) =
-            block_to_add_to.add(node_id, cost)
-        {
-            self.insert_blocks[i] = left_block;
-            self.insert_blocks.insert(i + 1, right_block);
+        let index = block_to_add_to.nodes.len();
+        match block_to_add_to.add(node_id, cost, &mut self.spare) {
+            BlockAdditionResult::Success => {
+                self.cost_map
+                    .insert(node_id, (BlockLocation::Insert(cost), index));
+            }
+            BlockAdditionResult::Split(right_block) => {
+                self.work
+                    .add(block_to_add_to.nodes.len() + right_block.nodes.len());
+                index_block(&mut self.cost_map, block_to_add_to, false);
+                index_block(&mut self.cost_map, &right_block, false);
+                self.insert_blocks.insert(i + 1, right_block);
+            }
         }
     }
 
-    fn get_minimum_block(&self) -> &Block {
-        self.prepend_blocks
-            .front()
-            .unwrap_or(self.insert_blocks.front().unwrap())
-    }
-
-    fn get_minimum_upper_bound(&self) -> Cost {
-        let block = self.get_minimum_block();
-        block
-            .nodes
-            .iter()
-            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Less))
-            .map_or(block.upper_bound, |&n| n.1)
-    }
-
-    pub fn batch_prepend(&mut self, nodes_to_prepend: Vec<(NodeId, Cost)>) {
-        // Remove any nodes that we might replace.
-        let mut nodes_to_actually_prepend = Vec::new();
-        for (node_id, cost) in &nodes_to_prepend {
-            if self.update(*node_id, *cost) {
-                nodes_to_actually_prepend.push((*node_id, *cost));
+    /// Prepends every element of `nodes_to_prepend`, leaving it empty (with its capacity).
+    pub fn batch_prepend(&mut self, nodes_to_prepend: &mut Vec<(NodeId, Cost)>) {
+        // Remove any nodes that we might replace, and keep the cheapest of any repeats.
+        let mut batch = std::mem::take(&mut self.batch);
+        batch.clear();
+        for (node_id, cost) in nodes_to_prepend.drain(..) {
+            if let Some(&(BlockLocation::Pending(old_cost), i)) = self.cost_map.get(&node_id) {
+                if cost < old_cost {
+                    batch[i].1 = cost;
+                    self.cost_map
+                        .insert(node_id, (BlockLocation::Pending(cost), i));
+                }
+            } else if self.update(node_id, cost) {
                 self.cost_map
-                    .insert(*node_id, BlockLocation::Prepend(*cost));
+                    .insert(node_id, (BlockLocation::Pending(cost), batch.len()));
+                batch.push((node_id, cost));
             }
         }
 
-        if nodes_to_actually_prepend.is_empty() {
-            // Return early!
-            return;
-        }
-
-        if nodes_to_actually_prepend.len() <= self.M {
-            // Just add a new block in the very front.
-            let upper_bound = self.get_minimum_upper_bound();
-            self.prepend_blocks.push_front(Block::from_existing(
-                self.M,
+        // Up to M nodes make one block; more are split into blocks of at most ceil(M/2).
+        let block_size = if batch.len() <= self.M {
+            self.M
+        } else {
+            self.M.div_ceil(2)
+        };
+        let moved = partition_into_chunks(&mut batch, block_size);
+        self.work.add(moved);
+        for chunk in batch.chunks(block_size).rev() {
+            self.work.add(chunk.len());
+            let mut nodes = spare_nodes(&mut self.spare);
+            nodes.extend_from_slice(chunk);
+            let upper_bound = nodes
+                .iter()
+                .map(|n| n.1)
+                .fold(Cost::NEG_INFINITY, Cost::max);
+            let tiebreak = self
+                .prepend_blocks
+                .front()
+                .map_or(usize::MAX, |block| block.tiebreak - 1);
+            let block = Block {
+                nodes,
                 upper_bound,
-                nodes_to_actually_prepend,
-            ));
-            return;
-        }
-        // Otherwise, we need to sort these nodes in reverse order and add them one by one into blocks.
-        // Technically we could do this in O(|nodes_to_actually_prepend|) with repeated medians, but we just sort
-        // here for simplicity.
-        nodes_to_actually_prepend.sort_by(|&a, &b| b.1.partial_cmp(&a.1).unwrap());
-        // Continually drain M elements and add into a new block until we're finished.
-        while !nodes_to_actually_prepend.is_empty() {
-            let block_nodes = nodes_to_actually_prepend
-                .drain(
-                    ..(((self.M as f64) / 2.0).ceil() as usize)
-                        .min(nodes_to_actually_prepend.len()),
-                )
-                .collect();
-            let upper_bound = self.get_minimum_upper_bound();
-            self.prepend_blocks
-                .push_front(Block::from_existing(self.M, upper_bound, block_nodes));
+                tiebreak,
+                capacity: self.M,
+            };
+            index_block(&mut self.cost_map, &block, true);
+            self.prepend_blocks.push_front(block);
         }
+        self.batch = batch;
     }
 
     // Returns the minimum cost across both block lists.
-    fn get_minimum_cost(&self) -> Cost {
+    fn get_minimum_cost(&mut self) -> Cost {
         let mut min_prepend = self.B;
         let mut min_insert = self.B;
         if let Some(block) = self.prepend_blocks.front() {
+            self.work.add(block.nodes.len());
             min_prepend = block
                 .nodes
                 .iter()
@@ -277,6 +454,7 @@ impl BlockList {
                 .map_or(block.upper_bound, |&n| n.1);
         }
         if let Some(block) = self.insert_blocks.front() {
+            self.work.add(block.nodes.len());
             min_insert = block
                 .nodes
                 .iter()
@@ -287,86 +465,120 @@ impl BlockList {
         min_prepend.min(min_insert)
     }
 
-    fn pull_elements(&mut self, num_to_pull: usize) -> Vec<usize> {
-        let mut prepend_block_elements = VecDeque::new();
-        let mut insert_block_elements = VecDeque::new();
-
-        // Consider some elements from the prepend list.
-        for p_i in 0..self.prepend_blocks.len() {
-            // Sort the nodes so we can take as many as needed.
-            let block_nodes = &mut self.prepend_blocks[p_i].nodes;
-            block_nodes.sort_by(|&a, &b| a.1.partial_cmp(&b.1).unwrap());
-            let num_to_take = num_to_pull.min(block_nodes.len());
-            for i in 0..num_to_take {
-                prepend_block_elements.push_back(block_nodes[i]);
-            }
-            if prepend_block_elements.len() == num_to_pull {
-                break;
+    // Moves every node costing at most `threshold` into `pulled`. Those are all in front blocks:
+    // once a block keeps a dearer node, the blocks behind it only hold dearer ones too.
+    fn take_up_to(&mut self, threshold: Cost, pulled: &mut Vec<NodeId>) {
+        for (blocks, in_d0) in [
+            (&mut self.prepend_blocks, true),
+            (&mut self.insert_blocks, false),
+        ] {
+            while let Some(block) = blocks.front_mut() {
+                let num_nodes = block.nodes.len();
+                self.work.add(num_nodes);
+                block.nodes.retain(|&(node_id, cost)| {
+                    if cost <= threshold {
+                        self.cost_map.remove(&node_id);
+                        pulled.push(node_id);
+                    }
+                    cost > threshold
+                });
+                if !block.nodes.is_empty() {
+                    if block.nodes.len() < num_nodes {
+                        index_block(&mut self.cost_map, block, in_d0);
+                    }
+                    break;
+                }
+                // D1 keeps its last block.
+                if !in_d0 && blocks.len() == 1 {
+                    break;
+                }
+                let block = blocks.pop_front().expect("checked above");
+                recycle(&mut self.spare, block.nodes);
             }
         }
+    }
 
-        // Consider some elements from the insert list.
-        for b_i in 0..self.insert_blocks.len() {
-            // Sort the nodes so we can take as many as needed.
-            let block_nodes = &mut self.insert_blocks[b_i].nodes;
-            block_nodes.sort_by(|&a, &b| a.1.partial_cmp(&b.1).unwrap());
-            let num_to_take = num_to_pull.min(block_nodes.len());
-            for i in 0..num_to_take {
-                insert_block_elements.push_back(block_nodes[i]);
-            }
-            if insert_block_elements.len() == num_to_pull {
-                break;
+    pub fn pull(&mut self) -> PullResult {
+        let mut pulled_elements = Vec::new();
+        let bound = self.pull_into(&mut pulled_elements);
+        PullResult(pulled_elements, bound)
+    }
+
+    /// `pull` into a caller-owned buffer, which is cleared first (in no particular order). Returns
+    /// the new lower bound of the remaining elements.
+    pub fn pull_into(&mut self, pulled_elements: &mut Vec<NodeId>) -> Cost {
+        pulled_elements.clear();
+        // Gather the front blocks of D0 and D1 until each has at least M nodes (or runs out). The
+        // M cheapest nodes are among those.
+        let candidates = &mut self.candidates;
+        candidates.clear();
+        for blocks in [&self.prepend_blocks, &self.insert_blocks] {
+            let start = candidates.len();
+            for block in blocks {
+                if candidates.len() - start >= self.M {
+                    break;
+                }
+                candidates.extend_from_slice(&block.nodes);
             }
         }
+        if !candidates.is_empty() {
+            self.work.add(candidates.len());
+            // Pull everything up to the M-th cheapest, and any others at its cost, so the bound
+            // stays strictly above everything pulled.
+            let threshold = if candidates.len() <= self.M {
+                candidates
+                    .iter()
+                    .map(|n| n.1)
+                    .fold(Cost::NEG_INFINITY, Cost::max)
+            } else {
+                select_cost(candidates, self.M - 1)
+            };
+            self.take_up_to(threshold, pulled_elements);
+        }
 
-        // Now we can effectively "merge" sort and pull from the appropriate list as needed.
-        let mut pulled_elements = Vec::new();
+        self.get_minimum_cost()
+    }
+}
 
-        while !(prepend_block_elements.is_empty() && insert_block_elements.is_empty())
-            && pulled_elements.len() < num_to_pull
-        {
-            let min_prepend_cost = prepend_block_elements.front().map_or(self.B, |&n| n.1);
-            let min_insert_cost = insert_block_elements.front().map_or(self.B, |&n| n.1);
+impl Default for BlockList {
+    fn default() -> Self {
+        Self::new(1, Cost::INFINITY)
+    }
+}
 
-            let node_id = if min_prepend_cost < min_insert_cost {
-                let (node_id, cost) = prepend_block_elements.pop_front().unwrap();
-                self.remove_from_prepend_list(node_id, cost);
-                node_id
-            } else {
-                let (node_id, cost) = insert_block_elements.pop_front().unwrap();
-                self.remove_from_insert_list(node_id, cost);
-                node_id
-            };
+impl BlockListOps for BlockList {
+    fn reset(&mut self, M: usize, B: Cost) {
+        BlockList::reset(self, M, B)
+    }
 
-            // Remove the node from our cost map.
-            self.cost_map.remove(&node_id);
-            pulled_elements.push(node_id);
-        }
-        pulled_elements
+    fn insert(&mut self, node_id: NodeId, cost: Cost) {
+        BlockList::insert(self, node_id, cost)
     }
 
-    pub fn pull(&mut self) -> PullResult {
-        let mut pulled_elements = Vec::new();
-        let mut num_elements_pulled = 0;
-        while num_elements_pulled < self.M {
-            // Try to drain up to a number of elements
-            let num_to_drain = self.M - num_elements_pulled;
-            let mut nodes = self.pull_elements(num_to_drain);
-            if nodes.is_empty() {
-                // We're done here.
-                break;
-            }
-            num_elements_pulled += nodes.len();
-            pulled_elements.append(&mut nodes);
-        }
+    fn batch_prepend(&mut self, nodes: &mut Vec<(NodeId, Cost)>) {
+        BlockList::batch_prepend(self, nodes)
+    }
 
-        PullResult(pulled_elements, self.get_minimum_cost())
+    fn pull(&mut self, pulled: &mut Vec<NodeId>) -> Cost {
+        self.pull_into(pulled)
+    }
+
+    fn is_empty(&self) -> bool {
+        BlockList::is_empty(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::Rng;
+
+    // Pulls come back in no particular order.
+    fn sorted_pull(block_list: &mut BlockList) -> (Vec<NodeId>, Cost) {
+        let PullResult(mut elements, upper_bound) = block_list.pull();
+        elements.sort_unstable();
+        (elements, upper_bound)
+    }
 
     #[test]
     fn it_works() {
@@ -377,9 +589,9 @@ mod tests {
     #[test]
     fn block_addition_no_split() {
         let mut block = Block::from_existing(4, 10.0, vec![(0, 1.0), (5, 5.0), (3, 3.0)]);
-        if let BlockAdditionResult::Success(block_added) = block.add(4, 4.0) {
-            assert_eq!(block_added.upper_bound, 10.0);
-            assert_eq!(block_added.nodes[3], (4, 4.0));
+        if let BlockAdditionResult::Success = block.add(4, 4.0, &mut Vec::new()) {
+            assert_eq!(block.upper_bound, 10.0);
+            assert_eq!(block.nodes[3], (4, 4.0));
         } else {
             panic!("We should have gotten a success!");
         }
@@ -388,9 +600,9 @@ mod tests {
     #[test]
     fn block_addition_triggers_split() {
         let mut block = Block::from_existing(3, 10.0, vec![(0, 1.0), (5, 5.0), (3, 3.0)]);
-        if let BlockAdditionResult::SplitBlocks(left_block, right_block) = block.add(4, 4.0) {
-            assert_eq!(left_block.upper_bound, 4.0);
-            assert_eq!(left_block.nodes.len(), 2);
+        if let BlockAdditionResult::Split(right_block) = block.add(4, 4.0, &mut Vec::new()) {
+            assert_eq!(block.upper_bound, 4.0);
+            assert_eq!(block.nodes.len(), 2);
             assert_eq!(right_block.upper_bound, 10.0);
             assert_eq!(right_block.nodes.len(), 2);
         } else {
@@ -426,18 +638,19 @@ mod tests {
         block_list.insert(10, 10.0);
 
         // Now prepend some values.
-        block_list.batch_prepend(vec![(8, 8.0), (7, 7.0), (9, 9.0)]);
+        block_list.batch_prepend(&mut vec![(8, 8.0), (7, 7.0), (9, 9.0)]);
         block_list.insert(50, 50.0);
         block_list.insert(60, 60.0);
-        block_list.batch_prepend(vec![(1, 1.0), (3, 3.0), (2, 2.0), (4, 4.0)]);
+        block_list.batch_prepend(&mut vec![(1, 1.0), (3, 3.0), (2, 2.0), (4, 4.0)]);
 
         // Now prepend some values.
         // Sorts into blocks into:
         // (D0) [1, 2] -> [3, 4] -> [7, 8, 9] -> (D1) [10, 30] -> [50, 60]
+        // A D0 block's upper bound is its most expensive node.
         assert_eq!(block_list.prepend_blocks.len(), 3);
-        assert_eq!(block_list.prepend_blocks[0].upper_bound, 3.0);
-        assert_eq!(block_list.prepend_blocks[1].upper_bound, 7.0);
-        assert_eq!(block_list.prepend_blocks[2].upper_bound, 10.0);
+        assert_eq!(block_list.prepend_blocks[0].upper_bound, 2.0);
+        assert_eq!(block_list.prepend_blocks[1].upper_bound, 4.0);
+        assert_eq!(block_list.prepend_blocks[2].upper_bound, 9.0);
 
         assert_eq!(block_list.insert_blocks.len(), 2);
         assert_eq!(block_list.insert_blocks[0].upper_bound, 50.0);
@@ -452,44 +665,256 @@ mod tests {
         block_list.insert(10, 10.0);
 
         // Now prepend some values.
-        block_list.batch_prepend(vec![(8, 8.0), (7, 7.0), (9, 9.0)]);
+        block_list.batch_prepend(&mut vec![(8, 8.0), (7, 7.0), (9, 9.0)]);
         block_list.insert(50, 50.0);
         block_list.insert(60, 60.0);
-        block_list.batch_prepend(vec![(1, 1.0), (3, 3.0), (2, 2.0), (4, 4.0)]);
+        block_list.batch_prepend(&mut vec![(1, 1.0), (3, 3.0), (2, 2.0), (4, 4.0)]);
 
         // Now prepend some values.
         // Sorts into blocks into:
-        // (D0) [1] -> [2, 3, 4] -> [7, 8, 9] D1: [10, 30] -> [50, 60]
+        // (D0) [1, 2] -> [3, 4] -> [7, 8, 9] D1: [10, 30] -> [50, 60]
         assert_eq!(block_list.len(), 11);
 
         // Pull.
-        let PullResult(elements, upper_bound) = block_list.pull();
+        let (elements, upper_bound) = sorted_pull(&mut block_list);
         assert_eq!(elements, vec![1, 2, 3]);
         assert_eq!(upper_bound, 4.0);
         assert_eq!(block_list.len(), 8);
 
         // Pull again
-        let PullResult(elements, upper_bound) = block_list.pull();
+        let (elements, upper_bound) = sorted_pull(&mut block_list);
         assert_eq!(elements, vec![4, 7, 8]);
         assert_eq!(upper_bound, 9.0);
         assert_eq!(block_list.len(), 5);
 
         // Pull again
-        let PullResult(elements, upper_bound) = block_list.pull();
+        let (elements, upper_bound) = sorted_pull(&mut block_list);
         assert_eq!(elements, vec![9, 10, 30]);
         assert_eq!(upper_bound, 50.0);
         assert_eq!(block_list.len(), 2);
 
         // Pull again (now we've run out of elements)
-        let PullResult(elements, upper_bound) = block_list.pull();
+        let (elements, upper_bound) = sorted_pull(&mut block_list);
         assert_eq!(elements, vec![50, 60]);
         assert_eq!(upper_bound, B);
-        assert_eq!(block_list.is_empty(), true);
+        assert!(block_list.is_empty());
 
         // Pulling from an empty list results in no elements.
-        let PullResult(elements, upper_bound) = block_list.pull();
+        let (elements, upper_bound) = sorted_pull(&mut block_list);
         assert_eq!(elements, vec![]);
         assert_eq!(upper_bound, B);
-        assert_eq!(block_list.is_empty(), true);
+        assert!(block_list.is_empty());
+    }
+
+    #[test]
+    fn matches_a_sorted_model() {
+        // Small integer costs, so pulls often end in a tie, and few node ids, so updates are common.
+        let B = 1e9;
+        let mut rng = Rng(11);
+        for M in [1, 2, 3, 8] {
+            let mut block_list = BlockList::new(M, B);
+            let mut model: HashMap<NodeId, Cost> = HashMap::new();
+            let mut bound = 0.0;
+            for _ in 0..3000 {
+                match rng.below(4) {
+                    0 | 1 => {
+                        let (node_id, cost) = (rng.below(200), bound + rng.below(20) as f64);
+                        block_list.insert(node_id, cost);
+                        let old = model.entry(node_id).or_insert(cost);
+                        *old = old.min(cost);
+                    }
+                    2 => {
+                        // At or below everything in the list.
+                        let min_cost = model.values().fold(bound + 10.0, |a, &b| a.min(b));
+                        let mut batch: Vec<_> = (0..rng.below(3 * M + 2))
+                            .map(|_| (rng.below(200), min_cost - rng.below(10) as f64))
+                            .collect();
+                        for &(node_id, cost) in &batch {
+                            let old = model.entry(node_id).or_insert(cost);
+                            *old = old.min(cost);
+                        }
+                        block_list.batch_prepend(&mut batch);
+                        assert!(batch.is_empty());
+                    }
+                    _ => {
+                        let mut costs: Vec<Cost> = model.values().copied().collect();
+                        costs.sort_by(f64::total_cmp);
+                        let threshold = costs.get(M - 1).or(costs.last()).copied();
+                        let mut expected: Vec<NodeId> = model
+                            .iter()
+                            .filter(|&(_, &cost)| Some(cost) <= threshold)
+                            .map(|(&node_id, _)| node_id)
+                            .collect();
+                        expected.sort_unstable();
+                        model.retain(|_, &mut cost| Some(cost) > threshold);
+                        let expected_bound = model.values().fold(B, |a, &b| a.min(b));
+
+                        let (pulled, new_bound) = sorted_pull(&mut block_list);
+                        assert_eq!(pulled, expected);
+                        assert_eq!(new_bound, expected_bound);
+                        bound = threshold.unwrap_or(bound);
+                    }
+                }
+                assert_eq!(block_list.len(), model.len());
+            }
+        }
+    }
+
+    #[test]
+    fn inserts_take_amortized_constant_work() {
+        // A split moves M + 1 nodes once per M/2 inserts or so, however big M is.
+        let N = 1 << 16;
+        for M in [8, 64, 512] {
+            let mut rng = Rng(12);
+            let mut block_list = BlockList::new(M, f64::INFINITY);
+            for node_id in 0..N {
+                block_list.insert(node_id, rng.below(1_000_000) as f64);
+            }
+            assert!(
+                block_list.work.0 <= 3 * N,
+                "M = {}: {} work for {} inserts",
+                M,
+                block_list.work.0,
+                N
+            );
+            // Every block is at most full, and the blocks are in order.
+            let blocks = &block_list.insert_blocks;
+            for (i, block) in blocks.iter().enumerate() {
+                assert!(block.nodes.len() <= M);
+                let lower_bound = if i == 0 {
+                    0.0
+                } else {
+                    blocks[i - 1].upper_bound
+                };
+                assert!(block
+                    .nodes
+                    .iter()
+                    .all(|n| lower_bound <= n.1 && n.1 <= block.upper_bound));
+            }
+        }
+    }
+
+    #[test]
+    fn updates_among_ties_take_amortized_constant_work() {
+        // Four costs in D1 and two in D0, so thousands of nodes share each one. A node's block is
+        // found by binary search (remove checks it in debug builds), not by scanning the blocks
+        // whose upper bound ties with its cost.
+        let N = 1 << 14;
+        for M in [8, 64, 512] {
+            let mut block_list = BlockList::new(M, f64::INFINITY);
+            for node_id in 0..N {
+                block_list.insert(node_id, 10.0 + (node_id % 4) as f64);
+            }
+            block_list.work = Work::default();
+            for node_id in 0..N {
+                block_list.insert(node_id, 5.0 + (node_id % 4) as f64);
+            }
+            let mut batch: Vec<_> = (N..2 * N).map(|n| (n, 1.0 + (n % 2) as f64)).collect();
+            block_list.batch_prepend(&mut batch);
+            let prepend_work = block_list.work.0;
+            for node_id in N..2 * N {
+                block_list.batch_prepend(&mut vec![(node_id, 0.5)]);
+            }
+            assert!(
+                block_list.work.0 - prepend_work <= 2 * N,
+                "M = {}: {} work for {} D0 updates",
+                M,
+                block_list.work.0 - prepend_work,
+                N
+            );
+            assert_eq!(block_list.len(), 2 * N);
+            let (pulled, _) = sorted_pull(&mut block_list);
+            assert_eq!(pulled, (N..2 * N).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn reset_reuses_allocations() {
+        // The same queries after a reset find a node Vec for every block they make.
+        let mut rng = Rng(15);
+        let mut block_list = BlockList::new(4, f64::INFINITY);
+        let ops: Vec<(usize, Cost)> = (0..500)
+            .map(|_| (rng.below(300), rng.below(50) as f64))
+            .collect();
+        let mut num_node_vecs = Vec::new();
+        for _ in 0..3 {
+            block_list.reset(4, f64::INFINITY);
+            let mut pulled = Vec::new();
+            for (i, &(node_id, cost)) in ops.iter().enumerate() {
+                if i % 50 == 49 {
+                    block_list.pull_into(&mut pulled);
+                    block_list.batch_prepend(&mut vec![(node_id, -(i as f64))]);
+                } else {
+                    block_list.insert(node_id, cost);
+                }
+            }
+            num_node_vecs.push(
+                block_list.spare.len()
+                    + block_list.prepend_blocks.len()
+                    + block_list.insert_blocks.len(),
+            );
+        }
+        assert_eq!(num_node_vecs[1], num_node_vecs[0]);
+        assert_eq!(num_node_vecs[2], num_node_vecs[0]);
+    }
+
+    #[test]
+    fn batch_prepends_take_l_log_l_over_m_work() {
+        // Each level of median splits moves all L nodes, and there are about log2(L / M) levels
+        // until the parts fit into blocks of ceil(M/2).
+        let M = 16;
+        for L in [M, 4 * M, 1 << 14] {
+            let mut rng = Rng(13);
+            let mut block_list = BlockList::new(M, f64::INFINITY);
+            let mut batch: Vec<_> = (0..L).map(|n| (n, rng.below(L) as f64)).collect();
+            block_list.batch_prepend(&mut batch);
+            let levels = (L as f64 / M as f64).log2() + 1.0;
+            assert!(
+                block_list.work.0 as f64 <= 2.0 * L as f64 * levels,
+                "L = {}: {} work",
+                L,
+                block_list.work.0
+            );
+            let blocks = &block_list.prepend_blocks;
+            let max_size = if L <= M { M } else { M.div_ceil(2) };
+            assert!(blocks.iter().all(|block| block.nodes.len() <= max_size));
+            for pair in blocks.iter().collect::<Vec<_>>().windows(2) {
+                assert!(pair[1].nodes.iter().all(|n| n.1 >= pair[0].upper_bound));
+            }
+            assert_eq!(block_list.len(), L);
+        }
+    }
+
+    #[test]
+    fn pulls_take_work_linear_in_what_they_pull() {
+        // Each pull gathers fewer than 4M candidates, scans at most 2M more to take the cheapest
+        // and find the new bound, and pulls at least M, so emptying the list costs O(N) in total,
+        // independently of M.
+        let N = 1 << 15;
+        for M in [4, 64, 1024] {
+            let mut rng = Rng(14);
+            let mut block_list = BlockList::new(M, f64::INFINITY);
+            for node_id in 0..N / 2 {
+                block_list.insert(node_id, 1000.0 + rng.below(1_000_000) as f64);
+            }
+            let mut batch: Vec<_> = (N / 2..N).map(|n| (n, rng.below(1000) as f64)).collect();
+            block_list.batch_prepend(&mut batch);
+            block_list.work = Work::default();
+            let mut pulled = Vec::new();
+            let mut num_pulled = 0;
+            while !block_list.is_empty() {
+                block_list.pull_into(&mut pulled);
+                assert!(pulled.len() >= M.min(block_list.len() + pulled.len()));
+                num_pulled += pulled.len();
+            }
+            assert_eq!(num_pulled, N);
+            assert!(
+                block_list.work.0 <= 8 * N,
+                "M = {}: {} work to pull {} nodes",
+                M,
+                block_list.work.0,
+                N
+            );
+        }
     }
 }
@@ -0,0 +1,73 @@
+// Common interface over the block list backends BMSSP can run on, so they can be swapped (and
+// benchmarked against each other) without editing the recursion:
+// - `block_data_structure`: the paper's linked blocks (D0 for batch prepends, D1 for inserts)
+// - `tree_block_list`: a BTreeSet ordered by (cost, node)
+// - `pq_block_list`: a binary heap with decrease-key
+
+// M and B follow the paper's notation.
+#![allow(non_snake_case)]
+
+use std::fmt;
+use std::str::FromStr;
+
+pub trait BlockListOps: Default {
+    /// Empties the list and sets new parameters, keeping allocations where the backend can.
+    fn reset(&mut self, M: usize, B: f64);
+
+    /// Adds `node_id` at `cost`, or lowers its cost if it is already in the list at a higher one.
+    fn insert(&mut self, node_id: usize, cost: f64);
+
+    /// Adds every element of `nodes`, all cheaper than anything already in the list, leaving it
+    /// empty (with its capacity).
+    fn batch_prepend(&mut self, nodes: &mut Vec<(usize, f64)>);
+
+    /// Moves the M cheapest nodes into `pulled` (cleared first), plus any others sharing the last
+    /// one's cost, and returns a bound strictly above everything pulled and at most the cost of
+    /// everything left (B once the list is empty).
+    fn pull(&mut self, pulled: &mut Vec<usize>) -> f64;
+
+    fn is_empty(&self) -> bool;
+}
+
+/// Block list selector for the command line (`--block-list paper|tree|pq`).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BlockListKind {
+    /// `block_data_structure::BlockList`.
+    Paper,
+    /// `tree_block_list::BlockList`, the fastest in practice.
+    #[default]
+    Tree,
+    /// `pq_block_list::BlockList`.
+    Pq,
+}
+
+impl BlockListKind {
+    pub const ALL: [BlockListKind; 3] =
+        [BlockListKind::Paper, BlockListKind::Tree, BlockListKind::Pq];
+}
+
+impl FromStr for BlockListKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "paper" => Ok(BlockListKind::Paper),
+            "tree" => Ok(BlockListKind::Tree),
+            "pq" => Ok(BlockListKind::Pq),
+            _ => Err(format!(
+                "Block list not found for input string: {}, possible options are: (\"paper\", \"tree\", \"pq\")",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for BlockListKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BlockListKind::Paper => "paper",
+            BlockListKind::Tree => "tree",
+            BlockListKind::Pq => "pq",
+        })
+    }
+}
@@ -1,197 +1,333 @@
-// BMSSSP (distance-only), implemented from
+// BMSSP (bounded multi-source shortest paths), implemented from
 // "Breaking the Sorting Barrier for Directed Single-Source Shortest Paths"
 // (Duan, Mao, Mao, Shu, Yin, April 2025) (arXiv:2504.17033v1)
-use crate::tree_block_list::{BlockList, PullResult};
-use hashbrown::{HashMap, HashSet};
-use std::cmp;
-use std::collections::BinaryHeap;
-
-#[derive(Copy, Clone, Debug, PartialEq)]
-struct State {
-    node_id: usize,
-    cost: f64,
+// Single-source, point-to-point (optionally goal-directed by a potential), multi-source and
+// distance-bounded searches, each returning a ShortestPathTree (distances and predecessors); the
+// `_with` variants reuse a QueryContext's buffers.
+// Variable names (B, M, N) follow the paper's notation.
+#![allow(non_snake_case)]
+use crate::algorithm::Bmssp;
+use crate::block_list::{BlockListKind, BlockListOps};
+use crate::graph::Graph;
+use crate::query_context::{QueryContext, State};
+use crate::shortest_path_tree::{MultiSourceTree, Predecessor, ShortestPathTree};
+use crate::{block_data_structure, pq_block_list, tree_block_list};
+use anyhow::{bail, Result};
+use std::fmt;
+
+// Buffers for one level of the recursion, kept in the QueryContext. Only one call per level is
+// running at a time, so each level can own its buffers. The block lists are kept apart, one Vec
+// per backend (see LevelBlockList).
+#[derive(Default)]
+pub(crate) struct Level {
+    pivots: Vec<usize>,
+    layer_set: Vec<usize>,
+    frontier: Vec<usize>,
+    batch_prepend_elements: Vec<(usize, f64)>,
 }
 
-impl State {
-    fn from(node_id: usize, cost: f64) -> Self {
-        Self { node_id, cost }
-    }
+/// BMSSP's parameters, in the paper's notation: find_pivots relaxes k steps out from the frontier,
+/// a call at level l pulls 2^((l-1)t) nodes at a time and returns at most k 2^(lt), and the search
+/// starts at level l.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BmsspParams {
+    pub k: usize,
+    pub t: usize,
+    pub l: usize,
 }
 
-// Min-heap by cost
-impl Eq for State {}
+impl BmsspParams {
+    /// The paper's values for a graph of N nodes: k = floor(log^(1/3) N) (at least 2),
+    /// t = floor(log^(2/3) N) and l = ceil(log N / t). k = 1 is valid, but has to be asked for.
+    pub fn for_graph(num_nodes: usize) -> Self {
+        let log_n = (num_nodes.max(1) as f64).log2();
+        let t = log_n.powf(2.0 / 3.0).floor() as usize;
+        Self {
+            k: log_n.powf(1.0 / 3.0).floor().max(2.0) as usize,
+            t,
+            l: Self::levels(num_nodes, t),
+        }
+    }
+
+    /// l = ceil(log N / t), so that 2^(lt) >= N. With t = 0 the levels don't grow, so there is one:
+    /// the base case.
+    fn levels(num_nodes: usize, t: usize) -> usize {
+        if t == 0 {
+            return 0;
+        }
+        ((num_nodes.max(1) as f64).log2() / t as f64).ceil() as usize
+    }
 
-impl Ord for State {
-    fn cmp(&self, other: &Self) -> cmp::Ordering {
-        // reverse ordering for min-heap
-        other
-            .cost
-            .partial_cmp(&self.cost)
-            .unwrap_or(cmp::Ordering::Equal)
+    /// `for_graph` with any of k, t and l replaced. Without an l, it follows t.
+    pub fn with_overrides(
+        num_nodes: usize,
+        k: Option<usize>,
+        t: Option<usize>,
+        l: Option<usize>,
+    ) -> Result<Self> {
+        let mut params = Self::for_graph(num_nodes);
+        params.k = k.unwrap_or(params.k);
+        params.t = t.unwrap_or(params.t);
+        params.l = l.unwrap_or(Self::levels(num_nodes, params.t));
+        params.check(num_nodes)?;
+        Ok(params)
+    }
+
+    /// Fails unless a search can settle all `num_nodes` nodes: the top level returns at most
+    /// k 2^(lt) of them.
+    pub fn check(&self, num_nodes: usize) -> Result<()> {
+        if self.k == 0 {
+            bail!("k must be at least 1");
+        }
+        if self.l > 64 {
+            bail!("l = {} is more levels than any graph needs", self.l);
+        }
+        if self.k.saturating_mul(pow2(self.l * self.t)) < num_nodes {
+            bail!(
+                "k 2^(lt) = {} 2^({} * {}) is below the {} nodes in the graph; raise k, t or l",
+                self.k,
+                self.l,
+                self.t,
+                num_nodes
+            );
+        }
+        Ok(())
     }
 }
 
-impl PartialOrd for State {
-    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
-        Some(self.cmp(other))
+impl fmt::Display for BmsspParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "k={} t={} l={}", self.k, self.t, self.l)
     }
 }
 
-// Returns a set of pivots and set W such that d(w) < B.
+// 2^exp, saturating at usize::MAX.
+fn pow2(exp: usize) -> usize {
+    u32::try_from(exp).map_or(usize::MAX, |exp| 2_usize.saturating_pow(exp))
+}
+
+// Per-query settings threaded through the recursion.
+struct Search {
+    k: usize,
+    t: usize,
+    // For point-to-point queries: once this node is complete the whole recursion unwinds.
+    target: Option<usize>,
+    target_settled: bool,
+}
+
+// Fills `pivots` and the set W (`layer_set`, nodes with d(w) < B).
 pub fn find_pivots(
     bound: f64,
     frontier: &[usize],
     k: usize,
-    neighbors: &Vec<Vec<(usize, f64)>>,
-    min_cost_map: &mut [f64],
-) -> (Vec<usize>, Vec<usize>) {
-    // Build out the "lookahead" layers in our search k-times forward from the frontier.
-    let mut layers = Vec::new();
-    layers.push(frontier.to_vec());
-    let mut all_layers = layers[0].clone();
-    let total_added = layers[0].len();
-    let mut bp_map = HashMap::new();
-    let mut last_layer = &layers[0];
-    for i in 1..=k {
-        let mut new_layer = Vec::new();
-        for &node_id in last_layer {
+    graph: &Graph,
+    ctx: &mut QueryContext,
+    pivots: &mut Vec<usize>,
+    layer_set: &mut Vec<usize>,
+) {
+    // Build out the "lookahead" layers in our search k-times forward from the frontier. They are
+    // stored one after the other in `layer_set`; `last_layer` is the range of the newest one.
+    layer_set.clear();
+    layer_set.extend_from_slice(frontier);
+    ctx.parents.clear();
+    // W is the frontier plus every node with a back pointer.
+    ctx.visited.clear();
+    ctx.visited.extend(frontier.iter().copied());
+    let mut last_layer = 0..frontier.len();
+    for _ in 1..=k {
+        let layer_start = layer_set.len();
+        for i in last_layer.clone() {
+            let node_id = layer_set[i];
             // Relax neighboring edges.
-            let cost_to_node_id = min_cost_map[node_id];
-            for &(neighbor_id, cost) in &neighbors[node_id] {
+            let cost_to_node_id = ctx.tree.dist[node_id];
+            for (edge_idx, neighbor_id, cost) in graph.edges(node_id) {
                 let cost_to_neighbor = cost_to_node_id + cost;
-                if cost_to_neighbor <= min_cost_map[neighbor_id] {
-                    min_cost_map[neighbor_id] = cost_to_neighbor;
-                    if cost_to_neighbor < bound {
+                let old_cost = ctx.tree.dist[neighbor_id];
+                if cost_to_neighbor <= old_cost {
+                    ctx.set(
+                        neighbor_id,
+                        cost_to_neighbor,
+                        Predecessor { node_id, edge_idx },
+                    );
+                    // A tie on a node already in W adds nothing. Taking it anyway would let a
+                    // zero-weight cycle point the back pointers in a loop.
+                    let in_w = |ctx: &QueryContext| {
+                        ctx.parents.contains_key(&neighbor_id) || ctx.visited.contains(&neighbor_id)
+                    };
+                    if cost_to_neighbor < bound && (cost_to_neighbor < old_cost || !in_w(ctx)) {
                         // Add to the layer!
-                        new_layer.push(neighbor_id);
+                        layer_set.push(neighbor_id);
                         // Keep back pointers so that we can traverse our forest to find pivots later.
-                        bp_map.insert(neighbor_id, node_id);
+                        ctx.parents.insert(neighbor_id, node_id);
                     }
                 }
             }
         }
-        all_layers.extend(new_layer.iter());
-        layers.push(new_layer);
-        last_layer = &layers[i];
+        last_layer = layer_start..layer_set.len();
         // If we're doing too much work we need to exit early.
-        if all_layers.len() > k * frontier.len() {
-            return (frontier.to_owned(), layers.into_iter().flatten().collect());
+        if layer_set.len() > k * frontier.len() {
+            pivots.clear();
+            pivots.extend_from_slice(frontier);
+            return;
         }
     }
 
     // Otherwise, we want to return the nodes in W0 (S) that are the roots of trees of size >= k.
     // We start by iterating through Wk and traversing until we hit a root and count the number of unique nodes in that tree.
-    let mut pivots = HashMap::new();
-    let mut node_to_root: HashMap<usize, usize> = HashMap::new();
-    for &leaf in layers.last().unwrap() {
+    ctx.tree_sizes.clear();
+    ctx.node_to_root.clear();
+    for &leaf in &layer_set[last_layer] {
         let mut cur = leaf;
-        let mut branch = vec![leaf];
-        while let Some(&next_node) = bp_map.get(&cur) {
-            if let Some(&root_id) = node_to_root.get(&next_node) {
+        ctx.branch.clear();
+        ctx.branch.push(leaf);
+        while let Some(&next_node) = ctx.parents.get(&cur) {
+            if let Some(&root_id) = ctx.node_to_root.get(&next_node) {
                 // This means we can break early. We just need to add the size of this branch to the pivots.
-                for &node_id in branch.iter() {
-                    node_to_root.insert(node_id, root_id);
+                for &node_id in ctx.branch.iter() {
+                    ctx.node_to_root.insert(node_id, root_id);
                 }
                 cur = root_id;
             } else {
-                branch.push(next_node);
+                ctx.branch.push(next_node);
                 cur = next_node;
             }
         }
 
         // This is a way to count the size of the tree (by adding branches uniquely).
-        pivots.insert(cur, pivots.get(&cur).unwrap_or(&0) + branch.len());
+        *ctx.tree_sizes.entry(cur).or_insert(0) += ctx.branch.len();
     }
 
     // Our pivots are any roots who have trees of size >= k.
-    let returned_pivots = pivots
-        .into_iter()
-        .filter(|kv| kv.1 >= k)
-        .map(|kv| kv.0)
-        .collect();
-    (
-        returned_pivots,
-        layers.into_iter().flatten().collect::<Vec<_>>(),
-    )
+    pivots.clear();
+    pivots.extend(
+        ctx.tree_sizes
+            .iter()
+            .filter(|kv| *kv.1 >= k)
+            .map(|kv| *kv.0),
+    );
+}
+
+// U is a set: a node the recursion already returned can come back through W, or through a stale
+// block list entry, and counting it twice would make a level think it's full.
+fn add_to_u(ctx: &mut QueryContext, u_set: &mut Vec<usize>, node_id: usize) {
+    if !ctx.settled[node_id] {
+        ctx.settled[node_id] = true;
+        u_set.push(node_id);
+    }
 }
 
 /*
-* Runs on l=0 off a singleton. Effectively a "mini-Dijkstra's".
-* One big assumption here is that node_id is closed.
-* Returns: a new boundary B' < upper_bound and a set U.
+* Runs on l=0 off a singleton, or off nodes that share a cost when Pull had to keep a tie together.
+* Effectively a "mini-Dijkstra's".
+* One big assumption here is that the frontier is closed.
+* Appends the set U to `u_set` and returns a new boundary B' < upper_bound.
+*
+* Once more than k + 1 nodes are settled, the search still settles every node tied with the
+* costliest one so far, and B' is the next label after them. The paper drops the costliest nodes
+* instead, which relies on all path lengths differing (its Assumption 2.1): with zero-weight edges
+* every node found can be at the frontier's own distance, U would come back empty and the caller
+* would pull the same frontier forever.
 */
 fn base_bmssp(
     upper_bound: f64,
-    node_id: usize,
-    k: usize,
-    neighbors: &Vec<Vec<(usize, f64)>>,
-    min_cost_map: &mut Vec<f64>,
-) -> (f64, Vec<usize>) {
-    let mut u_init = Vec::new();
-    let mut heap = BinaryHeap::new();
-    let mut visited_set = HashSet::new();
-    let mut max_cost_so_far = min_cost_map[node_id];
-    heap.push(State::from(node_id, max_cost_so_far));
-    while let Some(State { node_id, cost }) = heap.pop() {
-        if u_init.len() > k + 1 {
-            break;
-        }
-        if visited_set.contains(&node_id) {
+    frontier: &[usize],
+    graph: &Graph,
+    search: &mut Search,
+    ctx: &mut QueryContext,
+    u_set: &mut Vec<usize>,
+) -> f64 {
+    let u_start = u_set.len();
+    ctx.heap.clear();
+    let mut max_cost_so_far = f64::NEG_INFINITY;
+    let mut new_upper_bound = upper_bound;
+    for &node_id in frontier {
+        ctx.heap.push(State {
+            cost: ctx.tree.dist[node_id],
+            node_id,
+        });
+    }
+    while let Some(State { node_id, cost }) = ctx.heap.pop() {
+        // Settled here or by an earlier call, which already relaxed its edges.
+        if ctx.settled[node_id] {
             continue;
         }
-        visited_set.insert(node_id);
-        u_init.push(node_id);
-        max_cost_so_far = max_cost_so_far.max(min_cost_map[node_id]);
-        for &(neighbor_node_id, weight) in neighbors[node_id].iter() {
+        if u_set.len() - u_start > search.k + 1 && cost > max_cost_so_far {
+            // Everything cheaper than this node is settled.
+            new_upper_bound = cost;
+            break;
+        }
+        add_to_u(ctx, u_set, node_id);
+        max_cost_so_far = max_cost_so_far.max(cost);
+        for (edge_idx, neighbor_node_id, weight) in graph.edges(node_id) {
             let cost_to_neighbor = cost + weight;
-            if cost_to_neighbor <= min_cost_map[neighbor_node_id] && cost_to_neighbor < upper_bound
+            if cost_to_neighbor <= ctx.tree.dist[neighbor_node_id] && cost_to_neighbor < upper_bound
             {
-                min_cost_map[neighbor_node_id] = cost_to_neighbor;
-                heap.push(State::from(neighbor_node_id, cost_to_neighbor));
+                ctx.set(
+                    neighbor_node_id,
+                    cost_to_neighbor,
+                    Predecessor { node_id, edge_idx },
+                );
+                ctx.heap.push(State {
+                    cost: cost_to_neighbor,
+                    node_id: neighbor_node_id,
+                });
             }
         }
     }
 
-    if u_init.len() <= k {
-        (upper_bound, u_init)
-    } else {
-        (
-            max_cost_so_far,
-            u_init
-                .into_iter()
-                .filter(|node_id| min_cost_map[*node_id] < max_cost_so_far)
-                .collect(),
-        )
+    // Everything returned here is complete, so this is where the target gets settled.
+    if let Some(target) = search.target {
+        search.target_settled |= u_set[u_start..].contains(&target);
     }
+    new_upper_bound
 }
 
 /*
 * Requirements:
 * |frontier| <= 2^(l*t) ~ 4096 for the top level with 100k nodes.
 *
-* Returns: a new boundary B' < upper_bound and a set U.
+* Appends the set U to `u_set` and returns a new boundary B' < upper_bound. `block_lists[l]` is this
+* level's block list; the levels below get the rest.
 */
-fn bmssp_bounded(
+#[allow(clippy::too_many_arguments)]
+fn bmssp_bounded<L: BlockListOps>(
     l: usize,
     upper_bound: f64,
     frontier: &[usize],
-    k: usize,
-    t: usize,
-    neighbors: &Vec<Vec<(usize, f64)>>,
-    min_cost_map: &mut Vec<f64>,
-) -> (f64, Vec<usize>) {
+    graph: &Graph,
+    search: &mut Search,
+    ctx: &mut QueryContext,
+    block_lists: &mut [L],
+    u_set: &mut Vec<usize>,
+) -> f64 {
+    if search.target_settled {
+        return upper_bound;
+    }
     if l == 0 {
-        assert_eq!(frontier.len(), 1);
-        return base_bmssp(upper_bound, frontier[0], k, neighbors, min_cost_map);
+        return base_bmssp(upper_bound, frontier, graph, search, ctx, u_set);
     }
 
-    let (pivots, layer_set) = find_pivots(upper_bound, frontier, k, neighbors, min_cost_map);
-    let M = 2_usize.pow((t * (l - 1)).try_into().unwrap());
-    let mut block_list = BlockList::new(M, upper_bound);
+    // This level's buffers, handed back before returning.
+    let mut level = std::mem::take(&mut ctx.levels[l]);
+    let (lower_block_lists, block_list) = block_lists.split_at_mut(l);
+    let block_list = &mut block_list[0];
+    find_pivots(
+        upper_bound,
+        frontier,
+        search.k,
+        graph,
+        ctx,
+        &mut level.pivots,
+        &mut level.layer_set,
+    );
+    // Never more than the whole graph, which keeps the block lists' allocations in check for a large
+    // l or t.
+    let M = pow2(search.t * (l - 1)).min(graph.num_nodes());
+    block_list.reset(M, upper_bound);
     // Add the pivots to the queue.
     let mut min_upper_bound = upper_bound;
-    for pivot in pivots {
-        let dist = min_cost_map[pivot];
+    for &pivot in &level.pivots {
+        let dist = ctx.tree.dist[pivot];
         if dist > upper_bound {
             assert!(
                 dist < upper_bound,
@@ -204,90 +340,332 @@ fn bmssp_bounded(
         min_upper_bound = min_upper_bound.min(dist);
     }
 
-    let max_size_u_set = k * 2_usize.pow((t * l).try_into().unwrap());
-    let mut u_set = Vec::with_capacity(max_size_u_set);
+    let max_size_u_set = search.k.saturating_mul(pow2(search.t * l));
+    let u_start = u_set.len();
 
-    while u_set.len() < max_size_u_set && !block_list.is_empty() {
-        let PullResult(new_frontier, current_upper_bound) = block_list.pull();
-        let (new_upper_bound, new_uset) = bmssp_bounded(
+    while u_set.len() - u_start < max_size_u_set && !block_list.is_empty() && !search.target_settled
+    {
+        let current_upper_bound = block_list.pull(&mut level.frontier);
+        let new_uset_start = u_set.len();
+        let new_upper_bound = bmssp_bounded(
             l - 1,
             current_upper_bound,
-            &new_frontier,
-            k,
-            t,
-            neighbors,
-            min_cost_map,
+            &level.frontier,
+            graph,
+            search,
+            ctx,
+            lower_block_lists,
+            u_set,
         );
         min_upper_bound = new_upper_bound;
-        let mut batch_prepend_elements = Vec::with_capacity(new_uset.len() + new_frontier.len());
-        for &node_id in new_uset.iter() {
-            u_set.push(node_id);
-            let cost_to_node = min_cost_map[node_id];
-            for &(neighbor_node_id, weight) in neighbors[node_id].iter() {
+        for &node_id in &u_set[new_uset_start..] {
+            let cost_to_node = ctx.tree.dist[node_id];
+            for (edge_idx, neighbor_node_id, weight) in graph.edges(node_id) {
                 let proposed_weight = cost_to_node + weight;
-                let neighbor_cost = &mut min_cost_map[neighbor_node_id];
-                if proposed_weight <= *neighbor_cost {
-                    *neighbor_cost = proposed_weight;
+                if proposed_weight <= ctx.tree.dist[neighbor_node_id] {
+                    ctx.set(
+                        neighbor_node_id,
+                        proposed_weight,
+                        Predecessor { node_id, edge_idx },
+                    );
                     if current_upper_bound <= proposed_weight && proposed_weight < upper_bound {
                         block_list.insert(neighbor_node_id, proposed_weight)
                     } else if new_upper_bound <= proposed_weight
                         && proposed_weight < current_upper_bound
                     {
                         // Element is cheaper than anything in the block_list currently, so we can batch prepend.
-                        batch_prepend_elements.push((neighbor_node_id, proposed_weight));
+                        level
+                            .batch_prepend_elements
+                            .push((neighbor_node_id, proposed_weight));
                     }
                 }
             }
         }
 
-        for node_id in new_frontier.iter() {
-            let cost = min_cost_map[*node_id];
+        for &node_id in level.frontier.iter() {
+            let cost = ctx.tree.dist[node_id];
             if new_upper_bound <= cost && cost < current_upper_bound {
                 // These frontier nodes are cheaper than anything in the block_list, and we can batch prepend.
-                batch_prepend_elements.push((*node_id, cost));
+                level.batch_prepend_elements.push((node_id, cost));
             }
         }
-        block_list.batch_prepend(batch_prepend_elements);
+        block_list.batch_prepend(&mut level.batch_prepend_elements);
     }
 
     // Add any elements in our layer_set that might have a distance estimate less than the min_upper_bound.
-    for node_id in layer_set {
-        let cost = min_cost_map[node_id];
+    for &node_id in &level.layer_set {
+        let cost = ctx.tree.dist[node_id];
         if cost < min_upper_bound {
-            u_set.push(node_id);
+            add_to_u(ctx, u_set, node_id);
+            search.target_settled |= search.target == Some(node_id);
         }
     }
 
-    // Return a new boundary and uset.
-    (min_upper_bound, u_set)
+    ctx.levels[l] = level;
+    // Return a new boundary; the uset is in u_set[u_start..].
+    min_upper_bound
 }
 
 // Convenience function to call from a single source ID.
-pub fn bmssp_all(neighbors: &Vec<Vec<(usize, f64)>>, start: usize) -> Vec<f64> {
-    let N = neighbors.len() as f64;
-    // TODO: Explore why k=1 loops infinitely. Probably some bad condition in the code.
-    let k = N.log2().powf(1.0 / 3.0).floor().max(2.0) as usize;
-    let t = N.log2().powf(2.0 / 3.0).floor() as usize;
-    let starting_l = (N.log2() / (t as f64)).ceil() as usize;
-    // Initialize min_cost_map to infinity.
-    let mut min_cost_map = vec![f64::INFINITY; N as usize];
-    min_cost_map[start] = 0.0;
-    let B = f64::INFINITY;
-    let (min_upper_bound, uset) = bmssp_bounded(
-        starting_l,
-        B,
-        &vec![start],
+pub fn bmssp_all(graph: &Graph, start: usize) -> Vec<f64> {
+    bmssp_tree(graph, start).dist
+}
+
+// Same as bmssp_all, but keeps the predecessor of every reached node so routes can be rebuilt.
+pub fn bmssp_tree(graph: &Graph, start: usize) -> ShortestPathTree {
+    bmssp_search(
+        graph,
+        &[(start, 0.0)],
+        None,
+        f64::INFINITY,
+        &Bmssp::default(),
+    )
+}
+
+// Bounded search: runs the top level with B = `max_cost` instead of infinity, so nothing at or past
+// the bound gets expanded. At the top level |U| can't hit its size limit, so B' = B and every node
+// with a label below `max_cost` is complete. Returns those nodes as `(node, distance)` in order of
+// increasing distance.
+pub fn bmssp_within(graph: &Graph, start: usize, max_cost: f64) -> Vec<(usize, f64)> {
+    if max_cost <= 0.0 {
+        return Vec::new();
+    }
+    settled_below(bmssp_bounded_tree(graph, start, max_cost), max_cost)
+}
+
+// The nodes of a bounded search's tree that are complete, by increasing distance.
+pub(crate) fn settled_below(tree: ShortestPathTree, max_cost: f64) -> Vec<(usize, f64)> {
+    let mut settled: Vec<(usize, f64)> = tree
+        .dist
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, dist)| dist < max_cost)
+        .collect();
+    settled.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+    settled
+}
+
+// The tree behind bmssp_within: labels below `max_cost` are final, the others upper bounds.
+pub(crate) fn bmssp_bounded_tree(graph: &Graph, start: usize, max_cost: f64) -> ShortestPathTree {
+    bmssp_search(graph, &[(start, 0.0)], None, max_cost, &Bmssp::default())
+}
+
+// Point-to-point query: stops as soon as `target` is complete. Only the distance to (and path to)
+// `target` is final in the returned tree; other labels are upper bounds.
+pub fn bmssp_to(graph: &Graph, start: usize, target: usize) -> ShortestPathTree {
+    bmssp_search(
+        graph,
+        &[(start, 0.0)],
+        Some(target),
+        f64::INFINITY,
+        &Bmssp::default(),
+    )
+}
+
+// Experimental goal-directed point-to-point query ("A* for BMSSP") with `config`'s block list and
+// parameters. Runs bmssp_to on the reduced
+// costs w(u, v) - h(u) + h(v), which stay non-negative for a consistent heuristic `potential` (see
+// astar::astar) and change every s-v path by the same h(v) - h(s), so shortest paths are kept. Edges
+// pointing towards the target get cheaper, which lets the search settle the target sooner.
+// Reweighting is a pass over every edge, so this only pays off when that's cheaper than the nodes
+// it saves settling. Distances are mapped back to the original costs; only the target's is final.
+pub fn bmssp_to_with_potential(
+    graph: &Graph,
+    start: usize,
+    target: usize,
+    potential: impl Fn(usize) -> f64,
+    config: &Bmssp,
+) -> ShortestPathTree {
+    let h: Vec<f64> = (0..graph.num_nodes()).map(potential).collect();
+    let (offsets, targets, weights) = graph.raw_parts();
+    let mut reduced = Vec::with_capacity(weights.len());
+    for u in 0..graph.num_nodes() {
+        for edge_idx in offsets[u]..offsets[u + 1] {
+            // Clamped, as rounding can push a tight edge just below zero.
+            reduced.push((weights[edge_idx] - h[u] + h[targets[edge_idx]]).max(0.0));
+        }
+    }
+    let reduced_graph = Graph::from_raw_parts(offsets.to_vec(), targets.to_vec(), reduced)
+        .expect("same CSR layout as the input graph");
+    let mut tree = bmssp_search(
+        &reduced_graph,
+        &[(start, 0.0)],
+        Some(target),
+        f64::INFINITY,
+        config,
+    );
+    for (node_id, dist) in tree.dist.iter_mut().enumerate() {
+        *dist += h[start] - h[node_id];
+    }
+    // Re-add the target's distance along its path so it is exactly the original cost.
+    if let Some(edges) = tree.edges_to(target) {
+        let mut cost = 0.0;
+        for Predecessor { edge_idx, .. } in edges {
+            let (head, weight) = graph.edge(edge_idx);
+            cost += weight;
+            tree.dist[head] = cost;
+        }
+    }
+    tree
+}
+
+// Multi-source query from `(source, initial_offset)` pairs: the distance to, and owner of, every
+// node's nearest source. All the sources go into the top-level frontier. That meets
+// bmssp_bounded's requirement: think of a virtual source with an edge of weight `initial_offset`
+// to each source. The first source on any shortest path from it is complete at its offset, and
+// |S| <= N <= 2^(l*t) at the top level.
+pub fn bmssp_multi(graph: &Graph, sources: &[(usize, f64)]) -> MultiSourceTree {
+    MultiSourceTree::new(bmssp_search(
+        graph,
+        sources,
+        None,
+        f64::INFINITY,
+        &Bmssp::default(),
+    ))
+}
+
+pub(crate) fn bmssp_search(
+    graph: &Graph,
+    sources: &[(usize, f64)],
+    target: Option<usize>,
+    B: f64,
+    config: &Bmssp,
+) -> ShortestPathTree {
+    let mut ctx = QueryContext::new();
+    bmssp_search_with(&mut ctx, graph, sources, target, B, config);
+    ctx.into_tree()
+}
+
+// Single-source (or point-to-point, with `target`) query that reuses `ctx`'s buffers, so repeated
+// queries stop allocating once they have grown. The result stays in the context.
+pub fn bmssp_with<'a>(
+    ctx: &'a mut QueryContext,
+    graph: &Graph,
+    start: usize,
+    target: Option<usize>,
+) -> &'a ShortestPathTree {
+    bmssp_search_with(
+        ctx,
+        graph,
+        &[(start, 0.0)],
+        target,
+        f64::INFINITY,
+        &Bmssp::default(),
+    );
+    ctx.tree()
+}
+
+// A block list backend, and where its per-level lists are kept in the context.
+trait LevelBlockList: BlockListOps {
+    fn block_lists(ctx: &mut QueryContext) -> &mut Vec<Self>;
+}
+
+impl LevelBlockList for block_data_structure::BlockList {
+    fn block_lists(ctx: &mut QueryContext) -> &mut Vec<Self> {
+        &mut ctx.paper_block_lists
+    }
+}
+
+impl LevelBlockList for tree_block_list::BlockList {
+    fn block_lists(ctx: &mut QueryContext) -> &mut Vec<Self> {
+        &mut ctx.tree_block_lists
+    }
+}
+
+impl LevelBlockList for pq_block_list::BlockList {
+    fn block_lists(ctx: &mut QueryContext) -> &mut Vec<Self> {
+        &mut ctx.pq_block_lists
+    }
+}
+
+// bmssp_bounded at level `l` on backend L. Its lists are taken out of the context for the call, so
+// the recursion can borrow them next to it.
+fn run_top_level<L: LevelBlockList>(
+    l: usize,
+    B: f64,
+    frontier: &[usize],
+    graph: &Graph,
+    search: &mut Search,
+    ctx: &mut QueryContext,
+    u_set: &mut Vec<usize>,
+) {
+    let mut block_lists = std::mem::take(L::block_lists(ctx));
+    if block_lists.len() <= l {
+        block_lists.resize_with(l + 1, L::default);
+    }
+    bmssp_bounded(l, B, frontier, graph, search, ctx, &mut block_lists, u_set);
+    *L::block_lists(ctx) = block_lists;
+}
+
+// Runs the search with `config`'s block list and parameters (the paper's, unless set). Panics if set
+// parameters don't cover the graph: the top level couldn't settle all of it (see `Bmssp::check`).
+pub(crate) fn bmssp_search_with(
+    ctx: &mut QueryContext,
+    graph: &Graph,
+    sources: &[(usize, f64)],
+    target: Option<usize>,
+    B: f64,
+    config: &Bmssp,
+) {
+    let N = graph.num_nodes();
+    let BmsspParams {
+        k,
+        t,
+        l: starting_l,
+    } = match config.params() {
+        Some(params) => {
+            if let Err(err) = params.check(N) {
+                panic!("{:#}", err);
+            }
+            params
+        }
+        None => BmsspParams::for_graph(N),
+    };
+    ctx.start(N, sources);
+    if ctx.levels.len() <= starting_l {
+        ctx.levels.resize_with(starting_l + 1, Level::default);
+    }
+    let mut frontier = std::mem::take(&mut ctx.levels[0].frontier);
+    frontier.clear();
+    frontier.extend(sources.iter().map(|&(source, _)| source));
+    frontier.sort_unstable();
+    frontier.dedup();
+    // The cheapest source is complete from the start.
+    let min_offset = frontier
+        .iter()
+        .map(|&source| ctx.tree.dist[source])
+        .fold(f64::INFINITY, f64::min);
+    let target_settled = target.is_some_and(|target| ctx.tree.dist[target] == min_offset);
+    let mut search = Search {
         k,
         t,
-        neighbors,
-        &mut min_cost_map,
+        target,
+        target_settled,
+    };
+    let mut u_set = std::mem::take(&mut ctx.u_set);
+    u_set.clear();
+    let run = match config.block_list {
+        BlockListKind::Paper => run_top_level::<block_data_structure::BlockList>,
+        BlockListKind::Tree => run_top_level::<tree_block_list::BlockList>,
+        BlockListKind::Pq => run_top_level::<pq_block_list::BlockList>,
+    };
+    run(
+        starting_l,
+        B,
+        &frontier,
+        graph,
+        &mut search,
+        ctx,
+        &mut u_set,
     );
-    min_cost_map
+    ctx.u_set = u_set;
+    ctx.levels[0].frontier = frontier;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::{random_graph, Rng};
 
     #[test]
     fn sample_graph() {
@@ -306,7 +684,7 @@ mod tests {
 
         let start = 0;
 
-        let dist = bmssp_all(&neighbors, start);
+        let dist = bmssp_all(&Graph::from_adjacency(neighbors), start);
 
         assert_eq!(dist[0], 0.0);
         assert_eq!(dist[1], 0.0);
@@ -320,4 +698,230 @@ mod tests {
         assert_eq!(dist[9], 7.0);
         assert_eq!(dist[10], 8.5);
     }
+
+    #[test]
+    fn sample_graph_tree() {
+        let mut neighbors = vec![Vec::new(); 11];
+        neighbors[0] = vec![(1, 0.0), (2, 1.0), (7, 5.0)];
+        neighbors[1] = vec![(3, 3.0), (4, 2.0)];
+        neighbors[2] = vec![(4, 3.0), (5, 2.0)];
+        neighbors[3] = vec![(6, 2.0)];
+        neighbors[4] = vec![(6, 2.0)];
+        neighbors[5] = vec![];
+        neighbors[6] = vec![(8, 3.5)];
+        neighbors[7] = vec![(9, 2.0)];
+        neighbors[8] = vec![(10, 1.0)];
+        neighbors[9] = vec![(10, 2.0)];
+        neighbors[10] = vec![];
+
+        let graph = Graph::from_adjacency(neighbors);
+        let tree = bmssp_tree(&graph, 0);
+
+        assert_eq!(tree.path_to(10), Some(vec![0, 1, 4, 6, 8, 10]));
+        assert_eq!(tree.path_to(9), Some(vec![0, 7, 9]));
+        assert_eq!(tree.path_to(5), Some(vec![0, 2, 5]));
+        // Every back pointer is a tight edge of the graph.
+        for (node_id, pred) in tree.pred.iter().enumerate() {
+            if let Some(Predecessor {
+                node_id: parent,
+                edge_idx,
+            }) = *pred
+            {
+                let (head, weight) = graph.edge(edge_idx);
+                assert_eq!(head, node_id);
+                assert_eq!(tree.dist[parent] + weight, tree.dist[node_id]);
+            }
+        }
+        assert_eq!(tree.pred[0], None);
+    }
+
+    #[test]
+    fn params_overrides() {
+        let paper = BmsspParams::for_graph(20_000);
+        assert_eq!(paper, BmsspParams { k: 2, t: 5, l: 3 });
+        // floor(6.6^(1/3)) = 1, raised to 2.
+        assert_eq!(BmsspParams::for_graph(100).k, 2);
+        let params = |k, t, l| BmsspParams::with_overrides(20_000, k, t, l);
+        assert_eq!(params(None, None, None).unwrap(), paper);
+        // l follows an overridden t: ceil(14.3 / 3) = 5.
+        assert_eq!(
+            params(Some(1), Some(3), None).unwrap(),
+            BmsspParams { k: 1, t: 3, l: 5 }
+        );
+        assert_eq!(
+            params(None, None, Some(4)).unwrap(),
+            BmsspParams { k: 2, t: 5, l: 4 }
+        );
+        assert!(params(Some(0), None, None).is_err());
+        // 2 2^(1 * 5) nodes at most.
+        assert!(params(None, None, Some(1)).is_err());
+        assert!(params(None, Some(0), None).is_err());
+        assert!(params(Some(20_000), Some(0), None).is_ok());
+        assert!(params(None, None, Some(65)).is_err());
+
+        // Bmssp checks them when they're set, and `check` against a later graph; on a graph they
+        // can't cover, queries panic.
+        let small = BmsspParams { k: 1, t: 1, l: 2 };
+        assert!(Bmssp::default().with_params(small, 5).is_err());
+        let bmssp = Bmssp::default().with_params(small, 4).unwrap();
+        let graph = Graph::from_edges(
+            6,
+            &[
+                (0, 1, 1.0),
+                (1, 2, 1.0),
+                (2, 3, 1.0),
+                (3, 4, 1.0),
+                (4, 5, 1.0),
+            ],
+        );
+        assert!(bmssp.check(&graph).is_err());
+        assert!(bmssp.check(&Graph::from_edges(4, &[])).is_ok());
+        let query = std::panic::catch_unwind(|| {
+            bmssp_search(&graph, &[(0, 0.0)], None, f64::INFINITY, &bmssp)
+        });
+        assert!(query.is_err());
+    }
+
+    #[test]
+    fn zero_weight_edges_terminate() {
+        // Every node the base case finds sits at the source's distance.
+        let graph = Graph::from_edges(3, &[(0, 1, 0.0), (1, 2, 0.0)]);
+        assert_eq!(bmssp_all(&graph, 0), vec![0.0, 0.0, 0.0]);
+        // The zero-weight self-loop on 5 made find_pivots point 5's back pointer at itself.
+        let graph = Graph::from_edges(
+            18,
+            &[
+                (0, 1, 1.0),
+                (1, 2, 1.0),
+                (3, 4, 1.0),
+                (3, 5, 93.0),
+                (6, 7, 1.0),
+                (8, 9, 1.0),
+                (10, 6, 1.0),
+                (11, 12, 1.0),
+                (11, 13, 7.0),
+                (14, 11, 1.0),
+                (7, 14, 1.0),
+                (2, 15, 1.0),
+                (2, 8, 20.0),
+                (9, 10, 1.0),
+                (9, 16, 1.0),
+                (5, 5, 0.0),
+                (12, 3, 1.0),
+                (12, 17, 1.0),
+            ],
+        );
+        assert_eq!(
+            bmssp_all(&graph, 0),
+            crate::dijkstra::dijkstra_all(&graph, 0)
+        );
+    }
+
+    #[test]
+    fn point_to_point_matches_full_search() {
+        // Deterministic pseudo-random sparse digraph (fine-grained weights, so no equal-cost ties).
+        let n = 300;
+        let graph = random_graph(n, 3, 42);
+        let dist = crate::dijkstra::dijkstra_all(&graph, 0);
+        for (target, &expected) in dist.iter().enumerate() {
+            let tree = bmssp_to(&graph, 0, target);
+            assert_eq!(tree.dist[target], expected, "target {}", target);
+        }
+    }
+
+    #[test]
+    fn within_returns_nodes_below_the_bound() {
+        let n = 300;
+        let graph = random_graph(n, 3, 11);
+        let dist = crate::dijkstra::dijkstra_all(&graph, 0);
+        let mut sorted: Vec<f64> = dist.iter().copied().filter(|d| d.is_finite()).collect();
+        sorted.sort_by(f64::total_cmp);
+
+        // Bounds exactly on a node's distance check the bound is strict.
+        for max_cost in [0.0, 1.0, sorted[10], sorted[n / 3], 250.0, f64::INFINITY] {
+            let mut expected: Vec<(usize, f64)> = dist
+                .iter()
+                .copied()
+                .enumerate()
+                .filter(|&(_, d)| d < max_cost)
+                .collect();
+            expected.sort_by(|a, b| a.1.total_cmp(&b.1));
+            let dijkstra = crate::dijkstra::dijkstra_within(&graph, 0, max_cost);
+            assert_eq!(dijkstra, expected, "bound {}", max_cost);
+            assert_eq!(
+                bmssp_within(&graph, 0, max_cost),
+                expected,
+                "bound {}",
+                max_cost
+            );
+        }
+    }
+
+    #[test]
+    fn potential_keeps_point_to_point_distances() {
+        // Nodes on a line at x = 0, 1, 2, ...; weights at least the gap between their endpoints, so
+        // |x(v) - x(target)| is a consistent heuristic.
+        let n = 200;
+        let mut rng = Rng(3);
+        let mut neighbors = vec![Vec::new(); n];
+        for (u, edges) in neighbors.iter_mut().enumerate() {
+            for _ in 0..3 {
+                let v = (u + n + rng.below(21) - 10) % n;
+                let gap = (u as f64 - v as f64).abs();
+                edges.push((v, gap + rng.below(1_000_000) as f64 / 100_000.0 + 0.1));
+            }
+        }
+        let graph = Graph::from_adjacency(neighbors);
+        let dist = crate::dijkstra::dijkstra_all(&graph, 100);
+        for target in [0, 37, 100, 101, 150, 199] {
+            let to_target = |v: usize| (v as f64 - target as f64).abs();
+            let tree = bmssp_to_with_potential(&graph, 100, target, to_target, &Bmssp::default());
+            assert_eq!(tree.dist[target], dist[target], "target {}", target);
+            let length: f64 = tree
+                .edges_to(target)
+                .unwrap()
+                .iter()
+                .map(|p| graph.edge(p.edge_idx).1)
+                .sum();
+            assert_eq!(length, dist[target], "target {}", target);
+        }
+    }
+
+    #[test]
+    fn multi_source_matches_nearest_source() {
+        let n = 400;
+        let graph = random_graph(n, 3, 7);
+        let sources = [(3, 0.0), (150, 12.25), (299, 3.125), (150, 40.0), (42, 1e6)];
+
+        let single: Vec<Vec<f64>> = sources
+            .iter()
+            .map(|&(source, _)| crate::dijkstra::dijkstra_all(&graph, source))
+            .collect();
+        let dijkstra = crate::dijkstra::dijkstra_multi(&graph, &sources);
+        let bmssp = bmssp_multi(&graph, &sources);
+        // Summation order differs between the single- and multi-source searches.
+        let close = |a: f64, b: f64| a == b || (a - b).abs() < 1e-9;
+        for node_id in 0..n {
+            let expected = sources
+                .iter()
+                .zip(&single)
+                .map(|(&(_, offset), dist)| offset + dist[node_id])
+                .fold(f64::INFINITY, f64::min);
+            for result in [&dijkstra, &bmssp] {
+                assert!(
+                    close(result.tree.dist[node_id], expected),
+                    "node {}",
+                    node_id
+                );
+                // The owner is a source whose own tree explains the distance.
+                if let Some(owner) = result.owner[node_id] {
+                    let i = sources.iter().position(|&(s, _)| s == owner).unwrap();
+                    let via_owner = result.tree.dist[owner] + single[i][node_id];
+                    assert!(close(via_owner, expected), "node {}", node_id);
+                } else {
+                    assert!(expected.is_infinite());
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use clap::{ArgGroup, Parser};
+use ssps::ch::ContractionHierarchy;
+use ssps::graph_cache::{self, GraphCache};
+use ssps::osm_loader::LoadOptions;
+use ssps::profile::Profile;
+use ssps::weighting::Weighting;
+use ssps::{csv_loader, osm_loader};
+use std::time::Instant;
+
+#[derive(Parser, Debug)]
+#[command(name = "build-graph")]
+#[command(about = "Parse an OSM .pbf (or an edge-list CSV) once and write a binary graph cache that the osm and csv binaries load with --graph.", long_about = None)]
+#[command(group(ArgGroup::new("input").required(true).args(["pbf", "csv"])))]
+struct Cli {
+    /// Path to the .osm.pbf file
+    #[arg(short, long)]
+    pbf: Option<String>,
+
+    /// Path to an edge-list .csv file
+    #[arg(short, long)]
+    csv: Option<String>,
+
+    /// Path of the cache file to write
+    #[arg(short, long)]
+    out: String,
+
+    /// Only include 'highway' ways (recommended). If false, attempts to include all linear ways.
+    #[arg(long, default_value_t = true)]
+    only_highways: bool,
+
+    /// Edge weights for a .pbf: segment length in meters, or travel time in seconds.
+    #[arg(short, long, default_value_t = Weighting::Distance)]
+    weight: Weighting,
+
+    /// Mode of travel deciding which ways are usable and in which direction. Without one, every
+    /// highway is used in the directions cars may take.
+    #[arg(long)]
+    profile: Option<Profile>,
+
+    /// Don't apply turn restrictions (type=restriction relations).
+    #[arg(long, default_value_t = false)]
+    ignore_turn_restrictions: bool,
+
+    /// Also contract the graph and write the contraction hierarchy here, for fast point-to-point
+    /// queries with `osm --ch`.
+    #[arg(long)]
+    ch: Option<String>,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    let cache = match (&cli.pbf, &cli.csv) {
+        (Some(pbf), _) => {
+            let checksum = graph_cache::file_checksum(pbf)?;
+            let options = LoadOptions {
+                only_highways: cli.only_highways,
+                weighting: cli.weight,
+                profile: cli.profile,
+                turn_restrictions: !cli.ignore_turn_restrictions,
+            };
+            let osm = osm_loader::load_pbf(pbf, options)?;
+            println!(
+                "Collected {} routable ways; loaded coordinates for {} nodes",
+                osm.num_ways,
+                osm.num_osm_nodes()
+            );
+            let restrictions = osm.restrictions;
+            println!(
+                "Turn restrictions: {} applied, {} skipped (via way), {} skipped (invalid or not routable)",
+                restrictions.applied, restrictions.via_way, restrictions.invalid
+            );
+            GraphCache::from_osm(&osm, checksum)
+        }
+        (None, Some(csv)) => {
+            let checksum = graph_cache::file_checksum(csv)?;
+            GraphCache::from_csv(csv_loader::load_csv(csv)?, checksum)
+        }
+        (None, None) => unreachable!("clap requires --pbf or --csv"),
+    };
+
+    cache.write(&cli.out)?;
+    println!(
+        "Wrote graph with {} nodes, {} directed edges to {}",
+        cache.graph.num_nodes(),
+        cache.graph.num_edges(),
+        cli.out
+    );
+
+    if let Some(ch_path) = &cli.ch {
+        let now = Instant::now();
+        let ch = ContractionHierarchy::build(&cache.graph);
+        ch.write(ch_path)?;
+        println!(
+            "Wrote contraction hierarchy with {} shortcuts to {} ({:.1} s)",
+            ch.num_shortcuts(),
+            ch_path,
+            now.elapsed().as_secs_f64()
+        );
+    }
+    Ok(())
+}
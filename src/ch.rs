@@ -0,0 +1,641 @@
+// Contraction Hierarchies (Geisberger, Sanders, Schultes, Delling 2008).
+//
+// Preprocessing contracts the nodes one at a time, least important first. Contracting v removes it
+// from the remaining graph and adds a shortcut u -> x for every pair of remaining neighbours whose
+// only shortest connection went through v; a bounded "witness" Dijkstra that avoids v decides
+// that. Importance is twice the edge difference (shortcuts added minus edges removed) plus the
+// number of already contracted neighbours. It is recomputed for the neighbours of every contracted
+// node, and lazily when a node comes up for contraction.
+//
+// Every edge ends up stored at its lower-ranked endpoint: upward edges for the forward search from
+// the source, and reversed downward edges for the backward search from the target. A query runs
+// Dijkstra upward from both ends and takes the best meeting point. Shortcuts remember their middle
+// node, so a route unpacks to original edge ids.
+//
+// File layout (little-endian): magic, format version, fingerprint of the graph the hierarchy was
+// built for, its node and edge counts, the ranks, then each of the upward and downward graphs as
+// CSR arrays plus one packed `Via` per edge.
+
+use crate::binary_io::*;
+use crate::graph::Graph;
+use crate::query_context::State;
+use crate::shortest_path_tree::Predecessor;
+use anyhow::{ensure, Context, Result};
+use fnv::{FnvHashMap, FnvHasher};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+const MAGIC: &[u8; 8] = b"SSPSCHRC";
+
+/// Bumped whenever the layout changes; files with another version are rejected.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Nodes a witness search may settle before giving up (and adding the shortcut).
+const WITNESS_SETTLE_LIMIT: usize = 500;
+
+/// The same when only counting shortcuts to rate a node.
+const ESTIMATE_SETTLE_LIMIT: usize = 50;
+
+/// What an edge of the hierarchy stands for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Via {
+    /// An edge of the original graph, by edge id.
+    Edge(usize),
+    /// A shortcut through this node, made up of the two hierarchy edges to and from it.
+    Node(usize),
+}
+
+impl Via {
+    fn pack(self) -> usize {
+        match self {
+            Via::Edge(edge_idx) => edge_idx << 1,
+            Via::Node(node_id) => (node_id << 1) | 1,
+        }
+    }
+
+    fn unpack(packed: usize) -> Self {
+        if packed & 1 == 0 {
+            Via::Edge(packed >> 1)
+        } else {
+            Via::Node(packed >> 1)
+        }
+    }
+}
+
+/// FNV-1a hash of a graph's CSR arrays, to tie a hierarchy to the graph it was built from.
+pub fn graph_fingerprint(graph: &Graph) -> u64 {
+    let (offsets, targets, weights) = graph.raw_parts();
+    let mut hasher = FnvHasher::default();
+    for &v in offsets.iter().chain(targets) {
+        hasher.write_u64(v as u64);
+    }
+    for &w in weights {
+        hasher.write_u64(w.to_bits());
+    }
+    hasher.finish()
+}
+
+/// The graph still being contracted: the cheapest edge per (tail, head) pair among the remaining
+/// nodes, in both directions.
+struct Contractor {
+    out: Vec<FnvHashMap<usize, (f64, Via)>>,
+    inc: Vec<FnvHashMap<usize, (f64, Via)>>,
+    contracted_neighbors: Vec<usize>,
+    // Witness search labels, reset after every search through `touched`.
+    dist: Vec<f64>,
+    touched: Vec<usize>,
+}
+
+impl Contractor {
+    fn new(graph: &Graph) -> Self {
+        let n = graph.num_nodes();
+        let mut contractor = Self {
+            out: vec![FnvHashMap::default(); n],
+            inc: vec![FnvHashMap::default(); n],
+            contracted_neighbors: vec![0; n],
+            dist: vec![f64::INFINITY; n],
+            touched: Vec::new(),
+        };
+        for u in 0..n {
+            for (edge_idx, v, w) in graph.edges(u) {
+                // Self-loops are never on a shortest path.
+                if u != v {
+                    contractor.add_edge(u, v, w, Via::Edge(edge_idx));
+                }
+            }
+        }
+        contractor
+    }
+
+    fn add_edge(&mut self, u: usize, v: usize, w: f64, via: Via) {
+        let cheaper = self.out[u].get(&v).is_none_or(|&(old, _)| w < old);
+        if cheaper {
+            self.out[u].insert(v, (w, via));
+            self.inc[v].insert(u, (w, via));
+        }
+    }
+
+    /// Labels `self.dist` with costs from `source` in the remaining graph without `avoid`, up to
+    /// `max_cost` or `settle_limit` settled nodes. The labels are costs of real paths, so a label
+    /// is an upper bound even where the search stopped early.
+    fn witness_search(&mut self, source: usize, avoid: usize, max_cost: f64, settle_limit: usize) {
+        for node_id in self.touched.drain(..) {
+            self.dist[node_id] = f64::INFINITY;
+        }
+        let mut heap = BinaryHeap::new();
+        self.dist[source] = 0.0;
+        self.touched.push(source);
+        heap.push(State {
+            cost: 0.0,
+            node_id: source,
+        });
+        let mut settled = 0;
+        while let Some(State { cost, node_id }) = heap.pop() {
+            if cost > self.dist[node_id] {
+                continue;
+            }
+            settled += 1;
+            if cost > max_cost || settled > settle_limit {
+                break;
+            }
+            for (&next, &(w, _)) in &self.out[node_id] {
+                let next_cost = cost + w;
+                if next != avoid && next_cost < self.dist[next] {
+                    if self.dist[next].is_infinite() {
+                        self.touched.push(next);
+                    }
+                    self.dist[next] = next_cost;
+                    heap.push(State {
+                        cost: next_cost,
+                        node_id: next,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Shortcuts `(from, to, cost)` that contracting `v` would need. A lower `settle_limit` gives
+    /// up on witnesses sooner, which is enough to estimate the count.
+    fn shortcuts(&mut self, v: usize, settle_limit: usize) -> Vec<(usize, usize, f64)> {
+        let mut shortcuts = Vec::new();
+        let incoming: Vec<(usize, f64)> = self.inc[v].iter().map(|(&u, &(w, _))| (u, w)).collect();
+        let outgoing: Vec<(usize, f64)> = self.out[v].iter().map(|(&x, &(w, _))| (x, w)).collect();
+        let max_out = outgoing.iter().map(|&(_, w)| w).fold(0.0, f64::max);
+        for (u, w_in) in incoming {
+            self.witness_search(u, v, w_in + max_out, settle_limit);
+            for &(x, w_out) in &outgoing {
+                let cost = w_in + w_out;
+                if x != u && self.dist[x] > cost {
+                    shortcuts.push((u, x, cost));
+                }
+            }
+        }
+        shortcuts
+    }
+
+    fn priority(&mut self, v: usize) -> i64 {
+        let num_shortcuts = self.shortcuts(v, ESTIMATE_SETTLE_LIMIT).len();
+        let removed = self.out[v].len() + self.inc[v].len();
+        2 * (num_shortcuts as i64 - removed as i64) + self.contracted_neighbors[v] as i64
+    }
+}
+
+/// Edges `(tail, head, weight, via)` as a CSR graph plus the `Via` of every edge id.
+fn build_graph(num_nodes: usize, mut edges: Vec<(usize, usize, f64, Via)>) -> (Graph, Vec<Via>) {
+    edges.sort_by_key(|&(u, _, _, _)| u);
+    let triples: Vec<(usize, usize, f64)> = edges.iter().map(|&(u, v, w, _)| (u, v, w)).collect();
+    let via = edges.into_iter().map(|(_, _, _, via)| via).collect();
+    (Graph::from_edges(num_nodes, &triples), via)
+}
+
+/// Runs Dijkstra from `source` over one half of the hierarchy until it runs out of edges, and
+/// returns every node reached with its cost. Not every cost is a shortest distance, but the
+/// highest-ranked node of a shortest path is reached at its true cost, which is all a meeting
+/// needs.
+fn upward_search(graph: &Graph, source: usize) -> Vec<(usize, f64)> {
+    let mut dist = FnvHashMap::default();
+    let mut heap = BinaryHeap::new();
+    dist.insert(source, 0.0);
+    heap.push(State {
+        cost: 0.0,
+        node_id: source,
+    });
+    while let Some(State { cost, node_id }) = heap.pop() {
+        if cost > dist[&node_id] {
+            continue;
+        }
+        for (next, w) in graph.neighbors(node_id) {
+            let next_cost = cost + w;
+            if dist.get(&next).is_none_or(|&d| next_cost < d) {
+                dist.insert(next, next_cost);
+                heap.push(State {
+                    cost: next_cost,
+                    node_id: next,
+                });
+            }
+        }
+    }
+    dist.into_iter().collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContractionHierarchy {
+    /// Position of every node in the contraction order.
+    pub rank: Vec<usize>,
+    /// Edges to higher-ranked nodes, for the forward search.
+    up: Graph,
+    up_via: Vec<Via>,
+    /// Edges from higher-ranked nodes, reversed (stored at their head), for the backward search.
+    down: Graph,
+    down_via: Vec<Via>,
+    fingerprint: u64,
+    num_original_edges: usize,
+}
+
+impl ContractionHierarchy {
+    /// Contracts every node of `graph`.
+    pub fn build(graph: &Graph) -> Self {
+        let n = graph.num_nodes();
+        let mut contractor = Contractor::new(graph);
+        let mut priority: Vec<i64> = (0..n).map(|v| contractor.priority(v)).collect();
+        let mut queue: BinaryHeap<Reverse<(i64, usize)>> =
+            (0..n).map(|v| Reverse((priority[v], v))).collect();
+
+        let mut contracted = vec![false; n];
+        let mut rank = vec![0; n];
+        let mut up_edges = Vec::new();
+        let mut down_edges = Vec::new();
+        let mut next_rank = 0;
+        while let Some(Reverse((queued, v))) = queue.pop() {
+            // Skip entries left behind by a priority update.
+            if contracted[v] || queued != priority[v] {
+                continue;
+            }
+            // Lazy update: the priority may have gone up since v was queued.
+            priority[v] = contractor.priority(v);
+            if queue
+                .peek()
+                .is_some_and(|&Reverse((next, _))| priority[v] > next)
+            {
+                queue.push(Reverse((priority[v], v)));
+                continue;
+            }
+
+            let shortcuts = contractor.shortcuts(v, WITNESS_SETTLE_LIMIT);
+            contracted[v] = true;
+            rank[v] = next_rank;
+            next_rank += 1;
+            let mut neighbors = Vec::new();
+            for (x, (w, via)) in std::mem::take(&mut contractor.out[v]) {
+                up_edges.push((v, x, w, via));
+                contractor.inc[x].remove(&v);
+                contractor.contracted_neighbors[x] += 1;
+                neighbors.push(x);
+            }
+            for (u, (w, via)) in std::mem::take(&mut contractor.inc[v]) {
+                down_edges.push((v, u, w, via));
+                contractor.out[u].remove(&v);
+                contractor.contracted_neighbors[u] += 1;
+                neighbors.push(u);
+            }
+            for (u, x, cost) in shortcuts {
+                contractor.add_edge(u, x, cost, Via::Node(v));
+            }
+            // The neighbours lost an edge and may have gained shortcuts, so re-rate them now.
+            neighbors.sort_unstable();
+            neighbors.dedup();
+            for u in neighbors {
+                priority[u] = contractor.priority(u);
+                queue.push(Reverse((priority[u], u)));
+            }
+        }
+
+        let (up, up_via) = build_graph(n, up_edges);
+        let (down, down_via) = build_graph(n, down_edges);
+        Self {
+            rank,
+            up,
+            up_via,
+            down,
+            down_via,
+            fingerprint: graph_fingerprint(graph),
+            num_original_edges: graph.num_edges(),
+        }
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.rank.len()
+    }
+
+    /// Shortcut edges added by the contraction.
+    pub fn num_shortcuts(&self) -> usize {
+        self.up_via
+            .iter()
+            .chain(&self.down_via)
+            .filter(|via| matches!(via, Via::Node(_)))
+            .count()
+    }
+
+    /// Fails unless the hierarchy was built from exactly this graph.
+    pub fn check_graph(&self, graph: &Graph) -> Result<()> {
+        ensure!(
+            self.num_nodes() == graph.num_nodes()
+                && self.num_original_edges == graph.num_edges()
+                && self.fingerprint == graph_fingerprint(graph),
+            "contraction hierarchy was built from a different graph; rebuild it with build-graph --ch"
+        );
+        Ok(())
+    }
+
+    /// Shortest route from `start` to `target`: its cost and its edges in the original graph, in
+    /// order, or None if `target` is unreachable.
+    pub fn query(&self, start: usize, target: usize) -> Option<(f64, Vec<Predecessor>)> {
+        if start == target {
+            return Some((0.0, Vec::new()));
+        }
+        let sides = [(&self.up, start), (&self.down, target)];
+        let mut dist: [FnvHashMap<usize, f64>; 2] = Default::default();
+        // Per reached node: the node it was reached from and the hierarchy edge id used.
+        let mut pred: [FnvHashMap<usize, (usize, usize)>; 2] = Default::default();
+        let mut heaps = [BinaryHeap::new(), BinaryHeap::new()];
+        for (side, &(_, source)) in sides.iter().enumerate() {
+            dist[side].insert(source, 0.0);
+            heaps[side].push(State {
+                cost: 0.0,
+                node_id: source,
+            });
+        }
+
+        let mut best = f64::INFINITY;
+        let mut meeting = None;
+        loop {
+            let heads =
+                [heaps[0].peek(), heaps[1].peek()].map(|h| h.map_or(f64::INFINITY, |s| s.cost));
+            // Upward searches can't stop at the first meeting: a later one may be cheaper, until
+            // both queues are past the best cost found.
+            if heads[0].min(heads[1]) >= best {
+                break;
+            }
+            let side = if heads[0] <= heads[1] { 0 } else { 1 };
+            let State { cost, node_id } = heaps[side].pop().expect("head is below best");
+            if cost > dist[side][&node_id] {
+                continue;
+            }
+            if let Some(&other) = dist[1 - side].get(&node_id) {
+                if cost + other < best {
+                    best = cost + other;
+                    meeting = Some(node_id);
+                }
+            }
+            for (edge_idx, next, w) in sides[side].0.edges(node_id) {
+                let next_cost = cost + w;
+                if dist[side].get(&next).is_none_or(|&d| next_cost < d) {
+                    dist[side].insert(next, next_cost);
+                    pred[side].insert(next, (node_id, edge_idx));
+                    heaps[side].push(State {
+                        cost: next_cost,
+                        node_id: next,
+                    });
+                }
+            }
+        }
+
+        let meeting = meeting?;
+        // Hierarchy edges along the route, as (tail, head, weight, via) in original direction.
+        let mut ch_edges = Vec::new();
+        let mut cur = meeting;
+        while let Some(&(prev, edge_idx)) = pred[0].get(&cur) {
+            let (_, w) = self.up.edge(edge_idx);
+            ch_edges.push((prev, cur, w, self.up_via[edge_idx]));
+            cur = prev;
+        }
+        ch_edges.reverse();
+        let mut cur = meeting;
+        while let Some(&(next, edge_idx)) = pred[1].get(&cur) {
+            let (_, w) = self.down.edge(edge_idx);
+            ch_edges.push((cur, next, w, self.down_via[edge_idx]));
+            cur = next;
+        }
+
+        let mut route = Vec::new();
+        let mut cost = 0.0;
+        for edge in ch_edges {
+            self.unpack(edge, &mut route, &mut cost);
+        }
+        Some((cost, route))
+    }
+
+    /// Distances from every source to every target, one row per source (`f64::INFINITY` where
+    /// unreachable). Bucket-based: the backward search from each target leaves `(target, cost)` in
+    /// a bucket at every node it reaches, then the forward search from each source scans the
+    /// buckets of the nodes it reaches. That is one upward search per source and per target
+    /// rather than a query per pair.
+    pub fn many_to_many(&self, sources: &[usize], targets: &[usize]) -> Vec<Vec<f64>> {
+        let mut buckets: FnvHashMap<usize, Vec<(usize, f64)>> = FnvHashMap::default();
+        for (column, &target) in targets.iter().enumerate() {
+            for (node_id, cost) in upward_search(&self.down, target) {
+                buckets.entry(node_id).or_default().push((column, cost));
+            }
+        }
+        sources
+            .iter()
+            .map(|&source| {
+                let mut row = vec![f64::INFINITY; targets.len()];
+                for (node_id, cost) in upward_search(&self.up, source) {
+                    for &(column, other) in buckets.get(&node_id).into_iter().flatten() {
+                        row[column] = row[column].min(cost + other);
+                    }
+                }
+                row
+            })
+            .collect()
+    }
+
+    /// Appends the original edges behind a hierarchy edge to `route`, adding their weights to
+    /// `cost` in route order.
+    fn unpack(&self, edge: (usize, usize, f64, Via), route: &mut Vec<Predecessor>, cost: &mut f64) {
+        let mut stack = vec![edge];
+        while let Some((from, to, w, via)) = stack.pop() {
+            match via {
+                Via::Edge(edge_idx) => {
+                    route.push(Predecessor {
+                        node_id: from,
+                        edge_idx,
+                    });
+                    *cost += w;
+                }
+                Via::Node(middle) => {
+                    // from -> middle is a downward edge (stored at middle), middle -> to upward.
+                    let (first, w1) = self
+                        .down
+                        .edges(middle)
+                        .find(|&(_, head, _)| head == from)
+                        .map(|(e, _, w)| (self.down_via[e], w))
+                        .expect("shortcut halves are in the hierarchy");
+                    let (second, w2) = self
+                        .up
+                        .edges(middle)
+                        .find(|&(_, head, _)| head == to)
+                        .map(|(e, _, w)| (self.up_via[e], w))
+                        .expect("shortcut halves are in the hierarchy");
+                    stack.push((middle, to, w2, second));
+                    stack.push((from, middle, w1, first));
+                }
+            }
+        }
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        let file = File::create(path).with_context(|| format!("creating {}", path))?;
+        let mut w = BufWriter::new(file);
+        self.write_to(&mut w)
+            .with_context(|| format!("writing {}", path))?;
+        w.flush().with_context(|| format!("writing {}", path))
+    }
+
+    pub fn read(path: &str) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("opening {}", path))?;
+        Self::read_from(&mut BufReader::new(file)).with_context(|| format!("reading {}", path))
+    }
+
+    pub fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        w.write_all(MAGIC)?;
+        write_u32(w, FORMAT_VERSION)?;
+        write_u64(w, self.fingerprint)?;
+        write_u64(w, self.num_original_edges as u64)?;
+        write_usizes(w, &self.rank)?;
+        for (graph, via) in [(&self.up, &self.up_via), (&self.down, &self.down_via)] {
+            let (offsets, targets, weights) = graph.raw_parts();
+            write_usizes(w, offsets)?;
+            write_usizes(w, targets)?;
+            write_f64s(w, weights)?;
+            let packed: Vec<usize> = via.iter().map(|via| via.pack()).collect();
+            write_usizes(w, &packed)?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        ensure!(&magic == MAGIC, "not a contraction hierarchy file");
+        let version = read_u32(r)?;
+        ensure!(
+            version == FORMAT_VERSION,
+            "contraction hierarchy has format version {}, expected {}; rebuild it with build-graph --ch",
+            version,
+            FORMAT_VERSION
+        );
+        let fingerprint = read_u64(r)?;
+        let num_original_edges = read_u64(r)? as usize;
+        let rank = read_usizes(r)?;
+        let mut read_half = || -> Result<(Graph, Vec<Via>)> {
+            let offsets = read_usizes(r)?;
+            let targets = read_usizes(r)?;
+            let weights = read_f64s(r)?;
+            let graph = Graph::from_raw_parts(offsets, targets, weights)
+                .context("contraction hierarchy holds a malformed graph")?;
+            let via: Vec<Via> = read_usizes(r)?.into_iter().map(Via::unpack).collect();
+            ensure!(
+                graph.num_nodes() == rank.len()
+                    && via.len() == graph.num_edges()
+                    && via.iter().all(|via| match *via {
+                        Via::Edge(edge_idx) => edge_idx < num_original_edges,
+                        Via::Node(node_id) => node_id < rank.len(),
+                    }),
+                "contraction hierarchy edges don't match its {} nodes",
+                rank.len()
+            );
+            Ok((graph, via))
+        };
+        let (up, up_via) = read_half()?;
+        let (down, down_via) = read_half()?;
+        Ok(Self {
+            rank,
+            up,
+            up_via,
+            down,
+            down_via,
+            fingerprint,
+            num_original_edges,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dijkstra::dijkstra_all;
+    use crate::test_util::Rng;
+
+    fn local_graph(n: usize, seed: u64) -> Graph {
+        let mut rng = Rng(seed);
+        let mut neighbors = vec![Vec::new(); n];
+        for (u, edges) in neighbors.iter_mut().enumerate() {
+            for _ in 0..3 {
+                // Mostly local edges, like a road network, plus a parallel edge now and then.
+                let v = (u + rng.below(15)) % n;
+                edges.push((v, rng.weight()));
+            }
+        }
+        Graph::from_adjacency(neighbors)
+    }
+
+    #[test]
+    fn queries_match_dijkstra() {
+        let n = 250;
+        let graph = local_graph(n, 17);
+        let ch = ContractionHierarchy::build(&graph);
+        assert!(ch.num_shortcuts() > 0);
+        let mut ranks = ch.rank.clone();
+        ranks.sort_unstable();
+        assert_eq!(ranks, (0..n).collect::<Vec<_>>());
+
+        for start in [0, 1, 77, 249] {
+            let dist = dijkstra_all(&graph, start);
+            for (target, &expected) in dist.iter().enumerate() {
+                match ch.query(start, target) {
+                    None => assert!(expected.is_infinite(), "{} -> {}", start, target),
+                    Some((cost, route)) => {
+                        assert!((cost - expected).abs() < 1e-9, "{} -> {}", start, target);
+                        // The route is a chain of original edges from start to target.
+                        let mut cur = start;
+                        let mut length = 0.0;
+                        for Predecessor { node_id, edge_idx } in route {
+                            assert_eq!(node_id, cur);
+                            assert!(graph.edge_range(node_id).contains(&edge_idx));
+                            let (head, w) = graph.edge(edge_idx);
+                            length += w;
+                            cur = head;
+                        }
+                        assert_eq!(cur, target);
+                        assert_eq!(length, cost);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn many_to_many_matches_dijkstra() {
+        let graph = local_graph(200, 9);
+        let ch = ContractionHierarchy::build(&graph);
+        let sources = [3, 0, 150, 3];
+        let targets = [199, 3, 42, 7, 0];
+        let table = ch.many_to_many(&sources, &targets);
+        assert_eq!(table.len(), sources.len());
+        for (&source, row) in sources.iter().zip(&table) {
+            let dist = dijkstra_all(&graph, source);
+            assert_eq!(row.len(), targets.len());
+            for (&target, &cost) in targets.iter().zip(row) {
+                let expected = dist[target];
+                assert_eq!(cost.is_finite(), expected.is_finite());
+                if expected.is_finite() {
+                    assert!((cost - expected).abs() < 1e-9, "{} -> {}", source, target);
+                }
+            }
+        }
+        assert!(ch.many_to_many(&[], &targets).is_empty());
+        assert_eq!(ch.many_to_many(&sources, &[]), vec![Vec::<f64>::new(); 4]);
+    }
+
+    #[test]
+    fn round_trip_and_graph_check() {
+        let graph = local_graph(60, 4);
+        let ch = ContractionHierarchy::build(&graph);
+        let mut bytes = Vec::new();
+        ch.write_to(&mut bytes).unwrap();
+        let read = ContractionHierarchy::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read, ch);
+        assert!(read.check_graph(&graph).is_ok());
+        assert!(read.check_graph(&local_graph(60, 5)).is_err());
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[8] = 99;
+        assert!(ContractionHierarchy::read_from(&mut wrong_version.as_slice()).is_err());
+        assert!(ContractionHierarchy::read_from(&mut &bytes[..bytes.len() - 3]).is_err());
+    }
+}
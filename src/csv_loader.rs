@@ -0,0 +1,23 @@
+// Builds a Graph from a CSV of directed edges: node_id,neighbor_node_id,weight (with a header row).
+
+use crate::graph::Graph;
+use anyhow::Result;
+use csv::ReaderBuilder;
+
+pub fn load_csv(path: &str) -> Result<Graph> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true) // important: skip header line
+        .from_path(path)?;
+
+    let mut edges = Vec::new();
+
+    for result in rdr.records() {
+        let record = result?;
+        let node_id: usize = record[0].parse()?;
+        let neighbor_node_id: usize = record[1].parse()?;
+        let weight: f64 = record[2].parse()?;
+        edges.push((node_id, neighbor_node_id, weight));
+    }
+
+    Ok(Graph::from_edges(0, &edges))
+}
@@ -0,0 +1,360 @@
+// Randomized differential tests: BMSSP (on every block list backend) against Dijkstra on generated
+// graphs.
+//
+// Each generator covers a shape the handwritten tests don't: random sparse digraphs, grids,
+// zero-weight edges, parallel edges, disconnected components and equal-cost ties, plus graph sizes
+// around where BMSSP's k, t and recursion depth (all derived from N) change. Distances must match
+// exactly. A failing graph is shrunk (dropping edges and nodes, simplifying weights) before it is
+// reported, so the panic message is a small counterexample that can be pasted into a test.
+
+use crate::algorithm::{Bmssp, ShortestPathAlgorithm};
+use crate::block_list::BlockListKind;
+use crate::bmssp::BmsspParams;
+use crate::dijkstra::dijkstra_all;
+use crate::graph::Graph;
+use crate::test_util::{random_edges, Rng};
+use std::fmt;
+
+#[derive(Clone, Debug)]
+struct Case {
+    num_nodes: usize,
+    edges: Vec<(usize, usize, f64)>,
+    source: usize,
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Graph::from_edges({}, &[", self.num_nodes)?;
+        for (i, (u, v, w)) in self.edges.iter().enumerate() {
+            let sep = if i == 0 { "" } else { ", " };
+            write!(f, "{}({}, {}, {:?})", sep, u, v, w)?;
+        }
+        write!(f, "]) from source {}", self.source)
+    }
+}
+
+/// BMSSP with the default parameters on every block list.
+fn every_block_list() -> Vec<Bmssp> {
+    BlockListKind::ALL.into_iter().map(Bmssp::new).collect()
+}
+
+/// Why a case fails, or None if BMSSP agrees with Dijkstra on every node with every config.
+fn mismatch(case: &Case, configs: &[Bmssp]) -> Option<String> {
+    let graph = Graph::from_edges(case.num_nodes, &case.edges);
+    let expected = dijkstra_all(&graph, case.source);
+    configs.iter().find_map(|bmssp| {
+        let actual = bmssp.distances(&graph, case.source);
+        expected
+            .iter()
+            .zip(&actual)
+            .position(|(e, a)| e != a)
+            .map(|node_id| {
+                format!(
+                    "node {}: dijkstra {}, bmssp ({} block list, {:?}) {}",
+                    node_id,
+                    expected[node_id],
+                    bmssp.block_list,
+                    bmssp.params(),
+                    actual[node_id]
+                )
+            })
+    })
+}
+
+/// Renumbers the nodes used by an edge (and the source) to 0..m, dropping the rest.
+fn compact(case: &Case) -> Case {
+    let mut index = vec![usize::MAX; case.num_nodes];
+    let mut num_nodes = 0;
+    let mut number = |node_id: usize| {
+        if index[node_id] == usize::MAX {
+            index[node_id] = num_nodes;
+            num_nodes += 1;
+        }
+        index[node_id]
+    };
+    let source = number(case.source);
+    let edges = case
+        .edges
+        .iter()
+        .map(|&(u, v, w)| (number(u), number(v), w))
+        .collect();
+    Case {
+        num_nodes,
+        edges,
+        source,
+    }
+}
+
+/// Greedily shrinks a case for which `fails` holds, keeping every step that still fails: drops
+/// runs of edges (halving the run length down to single edges), drops unused nodes, and replaces
+/// weights with 1.0 or rounds them. Repeats until nothing more can be removed.
+fn shrink(mut case: Case, fails: impl Fn(&Case) -> bool) -> Case {
+    loop {
+        let before = (case.num_nodes, case.edges.len());
+        let mut chunk = case.edges.len().div_ceil(2);
+        while chunk > 0 {
+            let mut start = 0;
+            while start < case.edges.len() {
+                let mut candidate = case.clone();
+                let end = (start + chunk).min(candidate.edges.len());
+                candidate.edges.drain(start..end);
+                if fails(&candidate) {
+                    case = candidate;
+                } else {
+                    start += chunk;
+                }
+            }
+            chunk /= 2;
+        }
+        let compacted = compact(&case);
+        if compacted.num_nodes < case.num_nodes && fails(&compacted) {
+            case = compacted;
+        }
+        let mut weights_changed = false;
+        for i in 0..case.edges.len() {
+            let w = case.edges[i].2;
+            for simpler in [1.0, w.round()] {
+                if simpler != w {
+                    let mut candidate = case.clone();
+                    candidate.edges[i].2 = simpler;
+                    if fails(&candidate) {
+                        case = candidate;
+                        weights_changed = true;
+                        break;
+                    }
+                }
+            }
+        }
+        if before == (case.num_nodes, case.edges.len()) && !weights_changed {
+            return case;
+        }
+    }
+}
+
+/// Checks every case on every block list, panicking with the shrunk counterexample of the first
+/// failing one.
+fn check_all(cases: impl IntoIterator<Item = Case>) {
+    check_all_with(cases, &every_block_list());
+}
+
+fn check_all_with(cases: impl IntoIterator<Item = Case>, configs: &[Bmssp]) {
+    for case in cases {
+        if mismatch(&case, configs).is_some() {
+            let small = shrink(case, |c| mismatch(c, configs).is_some());
+            panic!(
+                "bmssp differs from dijkstra on {}: {}",
+                small,
+                mismatch(&small, configs).unwrap()
+            );
+        }
+    }
+}
+
+/// `rows x cols` grid with edges both ways between neighbours, weighted by `weight`.
+fn grid(rows: usize, cols: usize, mut weight: impl FnMut() -> f64) -> Vec<(usize, usize, f64)> {
+    let mut edges = Vec::new();
+    for r in 0..rows {
+        for c in 0..cols {
+            let u = r * cols + c;
+            if c + 1 < cols {
+                edges.push((u, u + 1, weight()));
+                edges.push((u + 1, u, weight()));
+            }
+            if r + 1 < rows {
+                edges.push((u, u + cols, weight()));
+                edges.push((u + cols, u, weight()));
+            }
+        }
+    }
+    edges
+}
+
+/// A few sources spread over the graph.
+fn sources(rng: &mut Rng, num_nodes: usize) -> Vec<usize> {
+    let mut sources = vec![0, num_nodes - 1];
+    sources.extend((0..2).map(|_| rng.below(num_nodes)));
+    sources.dedup();
+    sources
+}
+
+fn cases(
+    seed: u64,
+    rounds: usize,
+    mut generate: impl FnMut(&mut Rng) -> (usize, Vec<(usize, usize, f64)>),
+) -> Vec<Case> {
+    let mut rng = Rng(seed);
+    let mut cases = Vec::new();
+    for _ in 0..rounds {
+        let (num_nodes, edges) = generate(&mut rng);
+        for source in sources(&mut rng, num_nodes) {
+            cases.push(Case {
+                num_nodes,
+                edges: edges.clone(),
+                source,
+            });
+        }
+    }
+    cases
+}
+
+#[test]
+fn random_sparse_digraphs() {
+    check_all(cases(1, 20, |rng| {
+        let num_nodes = 50 + rng.below(400);
+        let out_degree = 1 + rng.below(4);
+        (num_nodes, random_edges(rng, num_nodes, out_degree))
+    }));
+}
+
+#[test]
+fn sizes_around_parameter_changes() {
+    // t = floor(log2(N)^(2/3)) steps up at N = 2, 8 (7.1..), 37 (36.7..) and 256; the recursion
+    // depth ceil(log2(N) / t) changes in between. N = 1 leaves t = 0.
+    let sizes = [
+        1, 2, 3, 4, 7, 8, 9, 16, 17, 36, 37, 38, 64, 65, 128, 255, 256, 257, 1024,
+    ];
+    check_all(sizes.iter().enumerate().flat_map(|(i, &num_nodes)| {
+        cases(100 + i as u64, 3, move |rng| {
+            (num_nodes, random_edges(rng, num_nodes, 3))
+        })
+    }));
+}
+
+#[test]
+fn zero_weight_edges() {
+    check_all(cases(2, 20, |rng| {
+        let num_nodes = 20 + rng.below(200);
+        let mut edges = random_edges(rng, num_nodes, 3);
+        for edge in edges.iter_mut() {
+            if rng.below(3) == 0 {
+                edge.2 = 0.0;
+            }
+        }
+        (num_nodes, edges)
+    }));
+}
+
+#[test]
+fn parallel_edges() {
+    check_all(cases(3, 20, |rng| {
+        let num_nodes = 20 + rng.below(200);
+        let mut edges = random_edges(rng, num_nodes, 2);
+        // Repeat some edges with other weights, and some with the same weight.
+        for i in 0..edges.len() {
+            let (u, v, w) = edges[i];
+            match rng.below(4) {
+                0 => edges.push((u, v, rng.weight())),
+                1 => edges.push((u, v, w)),
+                _ => {}
+            }
+        }
+        (num_nodes, edges)
+    }));
+}
+
+#[test]
+fn disconnected_components() {
+    check_all(cases(4, 20, |rng| {
+        // Several random components with no edges between them, plus isolated nodes.
+        let mut edges = Vec::new();
+        let mut num_nodes = 0;
+        for _ in 0..2 + rng.below(4) {
+            let size = 1 + rng.below(60);
+            for (u, v, w) in random_edges(rng, size, 2) {
+                edges.push((num_nodes + u, num_nodes + v, w));
+            }
+            num_nodes += size + rng.below(3);
+        }
+        (num_nodes, edges)
+    }));
+}
+
+#[test]
+fn weighted_grids() {
+    check_all(cases(5, 10, |rng| {
+        let (rows, cols) = (2 + rng.below(20), 2 + rng.below(20));
+        (rows * cols, grid(rows, cols, || rng.weight()))
+    }));
+}
+
+#[test]
+fn equal_cost_ties() {
+    // Small integer weights: many paths share a cost, and sums are exact.
+    check_all(cases(6, 10, |rng| {
+        let (rows, cols) = (2 + rng.below(20), 2 + rng.below(20));
+        (rows * cols, grid(rows, cols, || 1.0))
+    }));
+    check_all(cases(7, 20, |rng| {
+        let num_nodes = 20 + rng.below(300);
+        let mut edges = random_edges(rng, num_nodes, 3);
+        for edge in edges.iter_mut() {
+            edge.2 = (1 + rng.below(3)) as f64;
+        }
+        (num_nodes, edges)
+    }));
+}
+
+#[test]
+fn parameter_overrides() {
+    // Each covers the 300 nodes (k 2^(lt) >= 300): k = 1, small and large t, more levels than
+    // needed, and t = 0 with k = N, which is the base case alone.
+    let params = [
+        (1, 1, 9),
+        (1, 2, 5),
+        (2, 1, 12),
+        (3, 3, 3),
+        (8, 4, 2),
+        (300, 0, 0),
+    ];
+    let configs: Vec<Bmssp> = params
+        .iter()
+        .flat_map(|&(k, t, l)| {
+            every_block_list().into_iter().map(move |bmssp| {
+                bmssp
+                    .with_params(BmsspParams { k, t, l }, 300)
+                    .expect("covers 300 nodes")
+            })
+        })
+        .collect();
+    check_all_with(
+        cases(9, 5, |rng| {
+            let mut edges = random_edges(rng, 300, 3);
+            for edge in edges.iter_mut() {
+                if rng.below(4) == 0 {
+                    edge.2 = rng.below(3) as f64;
+                }
+            }
+            (300, edges)
+        }),
+        &configs,
+    );
+}
+
+#[test]
+fn shrinking_finds_a_small_counterexample() {
+    // A stand-in property that fails whenever node 0 reaches anything at cost above 10.
+    let fails = |case: &Case| {
+        let graph = Graph::from_edges(case.num_nodes, &case.edges);
+        dijkstra_all(&graph, case.source)
+            .iter()
+            .any(|&d| d.is_finite() && d > 10.0)
+    };
+    let mut rng = Rng(8);
+    let case = Case {
+        num_nodes: 200,
+        edges: random_edges(&mut rng, 200, 3),
+        source: 0,
+    };
+    assert!(fails(&case));
+    let small = shrink(case, fails);
+    // One edge from the source whose weight can't be simplified below the threshold.
+    assert_eq!(small.num_nodes, 2);
+    assert_eq!(small.edges.len(), 1);
+    let (u, v, w) = small.edges[0];
+    assert_eq!((u, v, small.source), (0, 1, 0));
+    assert!(w > 10.0 && w == w.round());
+    assert_eq!(
+        small.to_string(),
+        format!("Graph::from_edges(2, &[(0, 1, {:?})]) from source 0", w)
+    );
+}
@@ -1,35 +1,54 @@
-use std::cmp::Ordering;
+use crate::graph::Graph;
+use crate::query_context::{QueryContext, State};
+use crate::shortest_path_tree::{MultiSourceTree, Predecessor, ShortestPathTree};
 use std::collections::BinaryHeap;
 
-#[derive(Copy, Clone, PartialEq)]
-struct State {
-    cost: f64,
-    node_id: usize,
-}
+/// Dijkstra from `start` to all nodes. Returns distances (meters), `f64::INFINITY` when unreachable.
+pub fn dijkstra_all(graph: &Graph, start: usize) -> Vec<f64> {
+    let n = graph.num_nodes();
+    let mut dist = vec![f64::INFINITY; n];
+    let mut heap = BinaryHeap::new();
 
-// Min-heap by cost
-impl Eq for State {}
+    dist[start] = 0.0;
+    heap.push(State {
+        cost: 0.0,
+        node_id: start,
+    });
 
-impl Ord for State {
-    fn cmp(&self, other: &Self) -> Ordering {
-        // reverse ordering for min-heap
-        other
-            .cost
-            .partial_cmp(&self.cost)
-            .unwrap_or(Ordering::Equal)
+    while let Some(State { cost, node_id }) = heap.pop() {
+        if cost > dist[node_id] {
+            continue;
+        }
+        for (next, w) in graph.neighbors(node_id) {
+            let next_cost = cost + w;
+            if next_cost < dist[next] {
+                dist[next] = next_cost;
+                heap.push(State {
+                    cost: next_cost,
+                    node_id: next,
+                });
+            }
+        }
     }
+    dist
 }
 
-impl PartialOrd for State {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
+/// Dijkstra from `start` to all nodes, keeping the predecessor of every reached node.
+pub fn dijkstra_tree(graph: &Graph, start: usize) -> ShortestPathTree {
+    dijkstra_search(graph, &[(start, 0.0)], None)
 }
 
-/// Dijkstra from `start` to all nodes. Returns distances (meters), `f64::INFINITY` when unreachable.
-pub fn dijkstra_all(adj: &Vec<Vec<(usize, f64)>>, start: usize) -> Vec<f64> {
-    let n = adj.len();
-    let mut dist = vec![f64::INFINITY; n];
+/// Point-to-point Dijkstra: stops as soon as `target` is settled. Only the distance to (and path to)
+/// `target` is final in the returned tree.
+pub fn dijkstra_to(graph: &Graph, start: usize, target: usize) -> ShortestPathTree {
+    dijkstra_search(graph, &[(start, 0.0)], Some(target))
+}
+
+/// Dijkstra from `start` that stops once the cost reaches `max_cost`. Returns the settled nodes, those
+/// with a distance strictly below `max_cost`, as `(node, distance)` in order of increasing distance.
+pub fn dijkstra_within(graph: &Graph, start: usize, max_cost: f64) -> Vec<(usize, f64)> {
+    let mut dist = vec![f64::INFINITY; graph.num_nodes()];
+    let mut settled = Vec::new();
     let mut heap = BinaryHeap::new();
 
     dist[start] = 0.0;
@@ -39,12 +58,16 @@ pub fn dijkstra_all(adj: &Vec<Vec<(usize, f64)>>, start: usize) -> Vec<f64> {
     });
 
     while let Some(State { cost, node_id }) = heap.pop() {
+        if cost >= max_cost {
+            break;
+        }
         if cost > dist[node_id] {
             continue;
         }
-        for &(next, w) in &adj[node_id] {
+        settled.push((node_id, cost));
+        for (next, w) in graph.neighbors(node_id) {
             let next_cost = cost + w;
-            if next_cost < dist[next] {
+            if next_cost < dist[next] && next_cost < max_cost {
                 dist[next] = next_cost;
                 heap.push(State {
                     cost: next_cost,
@@ -53,5 +76,84 @@ pub fn dijkstra_all(adj: &Vec<Vec<(usize, f64)>>, start: usize) -> Vec<f64> {
             }
         }
     }
-    dist
+    settled
+}
+
+/// Dijkstra from several `(source, initial_offset)` pairs at once, as if from a virtual source with
+/// an edge of weight `initial_offset` to each of them. Every node gets the distance to, and the
+/// owner of, its nearest source.
+pub fn dijkstra_multi(graph: &Graph, sources: &[(usize, f64)]) -> MultiSourceTree {
+    MultiSourceTree::new(dijkstra_search(graph, sources, None))
+}
+
+/// Dijkstra from `start` (stopping once `target` is settled, if given) that reuses `ctx`'s
+/// buffers, so repeated queries stop allocating once they have grown. The result stays in the
+/// context.
+pub fn dijkstra_with<'a>(
+    ctx: &'a mut QueryContext,
+    graph: &Graph,
+    start: usize,
+    target: Option<usize>,
+) -> &'a ShortestPathTree {
+    dijkstra_search_with(ctx, graph, &[(start, 0.0)], target, f64::INFINITY);
+    ctx.tree()
+}
+
+/// `dijkstra_within` on `ctx`'s buffers. The nodes it would return are the ones labelled below
+/// `max_cost` in the returned tree; no other node is labelled at or beyond the bound.
+pub fn dijkstra_within_with<'a>(
+    ctx: &'a mut QueryContext,
+    graph: &Graph,
+    start: usize,
+    max_cost: f64,
+) -> &'a ShortestPathTree {
+    dijkstra_search_with(ctx, graph, &[(start, 0.0)], None, max_cost);
+    ctx.tree()
+}
+
+fn dijkstra_search(
+    graph: &Graph,
+    sources: &[(usize, f64)],
+    target: Option<usize>,
+) -> ShortestPathTree {
+    let mut ctx = QueryContext::new();
+    dijkstra_search_with(&mut ctx, graph, sources, target, f64::INFINITY);
+    ctx.into_tree()
+}
+
+// Stops at `target` if given, and doesn't label anything at or beyond `max_cost`.
+fn dijkstra_search_with(
+    ctx: &mut QueryContext,
+    graph: &Graph,
+    sources: &[(usize, f64)],
+    target: Option<usize>,
+    max_cost: f64,
+) {
+    ctx.start(graph.num_nodes(), sources);
+    for &(source, _) in sources {
+        let cost = ctx.tree.dist[source];
+        ctx.heap.push(State {
+            cost,
+            node_id: source,
+        });
+    }
+
+    while let Some(State { cost, node_id }) = ctx.heap.pop() {
+        if cost > ctx.tree.dist[node_id] {
+            continue;
+        }
+        if target == Some(node_id) {
+            break;
+        }
+        for (edge_idx, next, w) in graph.edges(node_id) {
+            let next_cost = cost + w;
+            if next_cost < ctx.tree.dist[next] && next_cost < max_cost {
+                ctx.set(next, next_cost, Predecessor { node_id, edge_idx });
+                ctx.heap.push(State {
+                    cost: next_cost,
+                    node_id: next,
+                });
+            }
+        }
+    }
 }
@@ -1,7 +1,9 @@
+/// Mean Earth radius in meters.
+pub const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
 /// Great-circle distance using the haversine formula.
 /// Input lat/lon in degrees. Output in meters.
 pub fn haversine_meters(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
-    let r = 6_371_000.0_f64; // Earth radius in meters
     let (lat1, lon1, lat2, lon2) = (
         lat1.to_radians(),
         lon1.to_radians(),
@@ -12,5 +14,5 @@ pub fn haversine_meters(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
     let dlon = lon2 - lon1;
     let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
     let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
-    r * c
+    EARTH_RADIUS_M * c
 }
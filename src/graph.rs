@@ -0,0 +1,258 @@
+// Directed, weighted graph over node indices 0..num_nodes, shared by all the SSP algorithms.
+//
+// Stored in compressed sparse row (CSR) form: the outgoing edges of node u are the edge ids
+// offsets[u]..offsets[u + 1], with their heads and weights packed in `targets` and `weights`.
+// Edge ids are stable, so a Predecessor's `edge_idx` points straight into these arrays.
+
+use std::ops::Range;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Graph {
+    offsets: Vec<usize>,
+    targets: Vec<usize>,
+    weights: Vec<f64>,
+}
+
+/// The incoming edges of a graph, for searches that run backwards from a target: edge `u -> v` of
+/// the original graph is edge `v -> u` here.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReverseGraph {
+    pub graph: Graph,
+    /// Original edge id of each reversed edge, so back pointers can name edges of the original.
+    pub forward_edge: Vec<usize>,
+}
+
+impl Default for Graph {
+    fn default() -> Self {
+        Self {
+            offsets: vec![0],
+            targets: Vec::new(),
+            weights: Vec::new(),
+        }
+    }
+}
+
+impl Graph {
+    pub fn from_adjacency(adj: Vec<Vec<(usize, f64)>>) -> Self {
+        let mut offsets = Vec::with_capacity(adj.len() + 1);
+        let num_edges = adj.iter().map(Vec::len).sum();
+        let mut targets = Vec::with_capacity(num_edges);
+        let mut weights = Vec::with_capacity(num_edges);
+        offsets.push(0);
+        for edges in adj {
+            for (v, w) in edges {
+                targets.push(v);
+                weights.push(w);
+            }
+            offsets.push(targets.len());
+        }
+        Self {
+            offsets,
+            targets,
+            weights,
+        }
+    }
+
+    /// Builds a graph from `(from, to, weight)` triples. Node count is the larger of `num_nodes` and
+    /// one past the highest index referenced by an edge. Edges out of the same node keep their
+    /// input order.
+    pub fn from_edges(num_nodes: usize, edges: &[(usize, usize, f64)]) -> Self {
+        let num_nodes = edges
+            .iter()
+            .map(|&(u, v, _)| u.max(v) + 1)
+            .max()
+            .unwrap_or(0)
+            .max(num_nodes);
+
+        // Counting sort by tail.
+        let mut offsets = vec![0; num_nodes + 1];
+        for &(u, _, _) in edges {
+            offsets[u + 1] += 1;
+        }
+        for i in 0..num_nodes {
+            offsets[i + 1] += offsets[i];
+        }
+        let mut next = offsets.clone();
+        let mut targets = vec![0; edges.len()];
+        let mut weights = vec![0.0; edges.len()];
+        for &(u, v, w) in edges {
+            targets[next[u]] = v;
+            weights[next[u]] = w;
+            next[u] += 1;
+        }
+        Self {
+            offsets,
+            targets,
+            weights,
+        }
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn num_edges(&self) -> usize {
+        self.targets.len()
+    }
+
+    /// Ids of the edges leaving `node_id`.
+    #[inline(always)]
+    pub fn edge_range(&self, node_id: usize) -> Range<usize> {
+        self.offsets[node_id]..self.offsets[node_id + 1]
+    }
+
+    /// `(head, weight)` of an edge id.
+    #[inline(always)]
+    pub fn edge(&self, edge_idx: usize) -> (usize, f64) {
+        (self.targets[edge_idx], self.weights[edge_idx])
+    }
+
+    /// Outgoing `(edge_idx, head, weight)` triples of `node_id`.
+    #[inline(always)]
+    pub fn edges(&self, node_id: usize) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+        let range = self.edge_range(node_id);
+        let start = range.start;
+        self.targets[range.clone()]
+            .iter()
+            .zip(&self.weights[range])
+            .enumerate()
+            .map(move |(i, (&v, &w))| (start + i, v, w))
+    }
+
+    /// Outgoing `(head, weight)` pairs of `node_id`.
+    #[inline(always)]
+    pub fn neighbors(&self, node_id: usize) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let range = self.edge_range(node_id);
+        self.targets[range.clone()]
+            .iter()
+            .copied()
+            .zip(self.weights[range].iter().copied())
+    }
+
+    pub fn out_degree(&self, node_id: usize) -> usize {
+        self.offsets[node_id + 1] - self.offsets[node_id]
+    }
+
+    /// Builds the incoming-edge graph.
+    pub fn reverse(&self) -> ReverseGraph {
+        let num_nodes = self.num_nodes();
+        // Counting sort by head.
+        let mut offsets = vec![0; num_nodes + 1];
+        for &v in &self.targets {
+            offsets[v + 1] += 1;
+        }
+        for i in 0..num_nodes {
+            offsets[i + 1] += offsets[i];
+        }
+        let mut next = offsets.clone();
+        let mut targets = vec![0; self.num_edges()];
+        let mut weights = vec![0.0; self.num_edges()];
+        let mut forward_edge = vec![0; self.num_edges()];
+        for u in 0..num_nodes {
+            for (edge_idx, v, w) in self.edges(u) {
+                targets[next[v]] = u;
+                weights[next[v]] = w;
+                forward_edge[next[v]] = edge_idx;
+                next[v] += 1;
+            }
+        }
+        ReverseGraph {
+            graph: Self {
+                offsets,
+                targets,
+                weights,
+            },
+            forward_edge,
+        }
+    }
+
+    /// The CSR arrays `(offsets, targets, weights)`, for serialization.
+    pub fn raw_parts(&self) -> (&[usize], &[usize], &[f64]) {
+        (&self.offsets, &self.targets, &self.weights)
+    }
+
+    /// Rebuilds a graph from arrays returned by `raw_parts`. Returns None unless they form a valid
+    /// CSR: `offsets` starts at 0, never decreases and ends at the edge count, and every target is
+    /// a node index.
+    pub fn from_raw_parts(
+        offsets: Vec<usize>,
+        targets: Vec<usize>,
+        weights: Vec<f64>,
+    ) -> Option<Self> {
+        let num_nodes = offsets.len().checked_sub(1)?;
+        let valid = offsets[0] == 0
+            && offsets.windows(2).all(|w| w[0] <= w[1])
+            && offsets[num_nodes] == targets.len()
+            && weights.len() == targets.len()
+            && targets.iter().all(|&v| v < num_nodes);
+        valid.then_some(Self {
+            offsets,
+            targets,
+            weights,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csr_from_edges() {
+        let graph = Graph::from_edges(5, &[(2, 0, 1.0), (0, 1, 2.0), (2, 1, 3.0), (0, 2, 4.0)]);
+        assert_eq!(graph.num_nodes(), 5);
+        assert_eq!(graph.num_edges(), 4);
+        assert_eq!(
+            graph.neighbors(0).collect::<Vec<_>>(),
+            vec![(1, 2.0), (2, 4.0)]
+        );
+        assert_eq!(graph.neighbors(1).count(), 0);
+        assert_eq!(
+            graph.edges(2).collect::<Vec<_>>(),
+            vec![(2, 0, 1.0), (3, 1, 3.0)]
+        );
+        assert_eq!(graph.edge(3), (1, 3.0));
+        assert_eq!(graph.out_degree(4), 0);
+
+        let adj = vec![
+            vec![(1, 2.0), (2, 4.0)],
+            vec![],
+            vec![(0, 1.0), (1, 3.0)],
+            vec![],
+            vec![],
+        ];
+        assert_eq!(Graph::from_adjacency(adj), graph);
+
+        let (offsets, targets, weights) = graph.raw_parts();
+        let rebuilt = Graph::from_raw_parts(offsets.to_vec(), targets.to_vec(), weights.to_vec());
+        assert_eq!(rebuilt, Some(graph));
+        assert_eq!(
+            Graph::from_raw_parts(vec![0, 2], vec![0, 1], vec![1.0, 1.0]),
+            None
+        );
+    }
+
+    #[test]
+    fn reverse_graph() {
+        let graph = Graph::from_edges(4, &[(2, 0, 1.0), (0, 1, 2.0), (2, 1, 3.0), (0, 2, 4.0)]);
+        let reverse = graph.reverse();
+        assert_eq!(reverse.graph.num_nodes(), 4);
+        assert_eq!(
+            reverse.graph.neighbors(1).collect::<Vec<_>>(),
+            vec![(0, 2.0), (2, 3.0)]
+        );
+        assert_eq!(
+            reverse.graph.neighbors(2).collect::<Vec<_>>(),
+            vec![(0, 4.0)]
+        );
+        assert_eq!(reverse.graph.neighbors(3).count(), 0);
+        for u in 0..4 {
+            for (edge_idx, v, w) in reverse.graph.edges(u) {
+                let forward = reverse.forward_edge[edge_idx];
+                assert_eq!(graph.edge(forward), (u, w));
+                assert!(graph.edge_range(v).contains(&forward));
+            }
+        }
+        assert_eq!(reverse.graph.reverse().graph, graph);
+    }
+}
@@ -0,0 +1,367 @@
+// Binary graph cache written by `build-graph`, so runs can skip parsing the source file.
+//
+// Layout (little-endian): magic, format version, source kind, FNV-1a checksum of the source file,
+// OSM load options, then the CSR arrays, the OSM node ids and the (lat, lon) of every node, and the
+// turn-restriction node copies. CSV-built caches have no node ids, coordinates or copies.
+
+use crate::binary_io::*;
+use crate::graph::Graph;
+use crate::osm_loader::{LoadOptions, OsmGraph};
+use crate::profile::Profile;
+use crate::turn_restrictions::RestrictionCounts;
+use crate::weighting::Weighting;
+use anyhow::{bail, ensure, Context, Result};
+use fnv::FnvHasher;
+use osmpbfreader::NodeId;
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+const MAGIC: &[u8; 8] = b"SSPSGRPH";
+
+/// Bumped whenever the layout changes; caches with another version are rejected.
+pub const FORMAT_VERSION: u32 = 4;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SourceKind {
+    Pbf,
+    Csv,
+}
+
+/// A graph read from (or about to be written to) a cache file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphCache {
+    pub source_kind: SourceKind,
+    /// `file_checksum` of the file the graph was built from.
+    pub source_checksum: u64,
+    /// Options the PBF was loaded with; the defaults for CSV-built caches.
+    pub options: LoadOptions,
+    pub graph: Graph,
+    pub idx_to_id: Vec<NodeId>,
+    pub coords: Vec<(f64, f64)>,
+    pub num_ways: usize,
+    /// See OsmGraph::copy_of.
+    pub copy_of: Vec<usize>,
+    pub restrictions: RestrictionCounts,
+}
+
+/// FNV-1a hash of the file contents, streamed so large extracts aren't read into memory.
+pub fn file_checksum(path: &str) -> Result<u64> {
+    let mut file = File::open(path).with_context(|| format!("opening {}", path))?;
+    let mut hasher = FnvHasher::default();
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .with_context(|| format!("reading {}", path))?;
+        if n == 0 {
+            break;
+        }
+        hasher.write(&buf[..n]);
+    }
+    Ok(hasher.finish())
+}
+
+impl GraphCache {
+    pub fn from_osm(osm: &OsmGraph, source_checksum: u64) -> Self {
+        Self {
+            source_kind: SourceKind::Pbf,
+            source_checksum,
+            options: osm.options,
+            graph: osm.graph.clone(),
+            idx_to_id: osm.idx_to_id.clone(),
+            coords: osm.coords.clone(),
+            num_ways: osm.num_ways,
+            copy_of: osm.copy_of.clone(),
+            restrictions: osm.restrictions,
+        }
+    }
+
+    pub fn from_csv(graph: Graph, source_checksum: u64) -> Self {
+        Self {
+            source_kind: SourceKind::Csv,
+            source_checksum,
+            options: LoadOptions::default(),
+            graph,
+            idx_to_id: Vec::new(),
+            coords: Vec::new(),
+            num_ways: 0,
+            copy_of: Vec::new(),
+            restrictions: RestrictionCounts::default(),
+        }
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        let file = File::create(path).with_context(|| format!("creating {}", path))?;
+        let mut w = BufWriter::new(file);
+        self.write_to(&mut w)
+            .with_context(|| format!("writing {}", path))?;
+        w.flush().with_context(|| format!("writing {}", path))
+    }
+
+    pub fn read(path: &str) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("opening {}", path))?;
+        Self::read_from(&mut BufReader::new(file)).with_context(|| format!("reading {}", path))
+    }
+
+    pub fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        w.write_all(MAGIC)?;
+        write_u32(w, FORMAT_VERSION)?;
+        write_u8(
+            w,
+            match self.source_kind {
+                SourceKind::Pbf => 0,
+                SourceKind::Csv => 1,
+            },
+        )?;
+        write_u64(w, self.source_checksum)?;
+        write_u8(w, self.options.only_highways as u8)?;
+        write_u8(
+            w,
+            match self.options.weighting {
+                Weighting::Distance => 0,
+                Weighting::Time => 1,
+            },
+        )?;
+        write_u8(
+            w,
+            match self.options.profile {
+                None => 0,
+                Some(Profile::Car) => 1,
+                Some(Profile::Bike) => 2,
+                Some(Profile::Foot) => 3,
+            },
+        )?;
+        write_u8(w, self.options.turn_restrictions as u8)?;
+        write_u64(w, self.num_ways as u64)?;
+
+        let (offsets, targets, weights) = self.graph.raw_parts();
+        write_usizes(w, offsets)?;
+        write_usizes(w, targets)?;
+        write_f64s(w, weights)?;
+
+        write_u64(w, self.idx_to_id.len() as u64)?;
+        for id in &self.idx_to_id {
+            write_u64(w, id.0 as u64)?;
+        }
+        write_u64(w, self.coords.len() as u64)?;
+        for &(lat, lon) in &self.coords {
+            write_f64(w, lat)?;
+            write_f64(w, lon)?;
+        }
+        write_usizes(w, &self.copy_of)?;
+        write_u64(w, self.restrictions.applied as u64)?;
+        write_u64(w, self.restrictions.via_way as u64)?;
+        write_u64(w, self.restrictions.invalid as u64)?;
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        ensure!(&magic == MAGIC, "not a graph cache file");
+        let version = read_u32(r)?;
+        ensure!(
+            version == FORMAT_VERSION,
+            "graph cache has format version {}, expected {}; rebuild it with build-graph",
+            version,
+            FORMAT_VERSION
+        );
+        let source_kind = match read_u8(r)? {
+            0 => SourceKind::Pbf,
+            1 => SourceKind::Csv,
+            other => bail!("unknown source kind {} in graph cache", other),
+        };
+        let source_checksum = read_u64(r)?;
+        let only_highways = read_u8(r)? != 0;
+        let weighting = match read_u8(r)? {
+            0 => Weighting::Distance,
+            1 => Weighting::Time,
+            other => bail!("unknown weighting {} in graph cache", other),
+        };
+        let profile = match read_u8(r)? {
+            0 => None,
+            1 => Some(Profile::Car),
+            2 => Some(Profile::Bike),
+            3 => Some(Profile::Foot),
+            other => bail!("unknown profile {} in graph cache", other),
+        };
+        let turn_restrictions = read_u8(r)? != 0;
+        let options = LoadOptions {
+            only_highways,
+            weighting,
+            profile,
+            turn_restrictions,
+        };
+        let num_ways = read_u64(r)? as usize;
+
+        let offsets = read_usizes(r)?;
+        let targets = read_usizes(r)?;
+        let weights = read_f64s(r)?;
+        let graph = Graph::from_raw_parts(offsets, targets, weights)
+            .context("graph cache holds a malformed graph")?;
+
+        let num_ids = read_u64(r)? as usize;
+        let idx_to_id = (0..num_ids)
+            .map(|_| read_u64(r).map(|id| NodeId(id as i64)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let num_coords = read_u64(r)? as usize;
+        let coords = (0..num_coords)
+            .map(|_| Ok((read_f64(r)?, read_f64(r)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let copy_of = read_usizes(r)?;
+        let restrictions = RestrictionCounts {
+            applied: read_u64(r)? as usize,
+            via_way: read_u64(r)? as usize,
+            invalid: read_u64(r)? as usize,
+        };
+        if source_kind == SourceKind::Pbf {
+            ensure!(
+                idx_to_id.len() + copy_of.len() == graph.num_nodes()
+                    && coords.len() == idx_to_id.len()
+                    && copy_of.iter().all(|&original| original < idx_to_id.len()),
+                "graph cache node ids/coordinates don't match its {} nodes",
+                graph.num_nodes()
+            );
+        }
+
+        Ok(Self {
+            source_kind,
+            source_checksum,
+            options,
+            graph,
+            idx_to_id,
+            coords,
+            num_ways,
+            copy_of,
+            restrictions,
+        })
+    }
+
+    /// Fails if `source_path` no longer matches the file the cache was built from.
+    pub fn check_source(&self, source_path: &str) -> Result<()> {
+        let checksum = file_checksum(source_path)?;
+        ensure!(
+            checksum == self.source_checksum,
+            "graph cache is stale: {} has changed since the cache was built; rebuild it with build-graph",
+            source_path
+        );
+        Ok(())
+    }
+
+    /// Fails if load options given on the command line contradict the ones the cache was built
+    /// with. Options left unset accept whatever the cache uses.
+    pub fn check_options(
+        &self,
+        path: &str,
+        weight: Option<Weighting>,
+        profile: Option<Profile>,
+        ignore_turn_restrictions: bool,
+    ) -> Result<()> {
+        if profile.is_some() && profile != self.options.profile {
+            bail!(
+                "graph cache {} was built {}",
+                path,
+                match self.options.profile {
+                    Some(profile) => format!("with --profile {}", profile),
+                    None => String::from("without --profile"),
+                }
+            );
+        }
+        if ignore_turn_restrictions && self.options.turn_restrictions {
+            bail!("graph cache {} was built with turn restrictions", path);
+        }
+        if let Some(weight) = weight {
+            if weight != self.options.weighting {
+                bail!(
+                    "graph cache {} uses --weight {}, not {}",
+                    path,
+                    self.options.weighting,
+                    weight
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn into_osm_graph(self) -> Result<OsmGraph> {
+        ensure!(
+            self.source_kind == SourceKind::Pbf,
+            "graph cache was built from a CSV and has no OSM node ids"
+        );
+        let id_to_idx = self
+            .idx_to_id
+            .iter()
+            .enumerate()
+            .map(|(idx, &id)| (id, idx))
+            .collect();
+        Ok(OsmGraph {
+            graph: self.graph,
+            idx_to_id: self.idx_to_id,
+            id_to_idx,
+            coords: self.coords,
+            num_ways: self.num_ways,
+            options: self.options,
+            copy_of: self.copy_of,
+            restrictions: self.restrictions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_cache() -> GraphCache {
+        GraphCache {
+            source_kind: SourceKind::Pbf,
+            source_checksum: 0xdead_beef,
+            options: LoadOptions {
+                only_highways: true,
+                weighting: Weighting::Time,
+                profile: Some(Profile::Bike),
+                turn_restrictions: true,
+            },
+            graph: Graph::from_edges(4, &[(0, 3, 1.5), (1, 2, 2.5), (2, 0, 0.25), (3, 2, 2.5)]),
+            idx_to_id: vec![NodeId(10), NodeId(-20), NodeId(30)],
+            coords: vec![(52.5, 13.4), (52.6, 13.5), (-33.9, 151.2)],
+            num_ways: 2,
+            copy_of: vec![1],
+            restrictions: RestrictionCounts {
+                applied: 1,
+                via_way: 2,
+                invalid: 3,
+            },
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let cache = sample_cache();
+        let mut bytes = Vec::new();
+        cache.write_to(&mut bytes).unwrap();
+        let read = GraphCache::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read, cache);
+
+        let osm = read.into_osm_graph().unwrap();
+        assert_eq!(osm.node_index(NodeId(-20)), Some(1));
+    }
+
+    #[test]
+    fn rejects_bad_headers_and_truncation() {
+        let mut bytes = Vec::new();
+        sample_cache().write_to(&mut bytes).unwrap();
+
+        let mut other_version = bytes.clone();
+        other_version[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        let err = GraphCache::read_from(&mut other_version.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("format version"));
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert!(GraphCache::read_from(&mut bad_magic.as_slice()).is_err());
+
+        let truncated = &bytes[..bytes.len() - 4];
+        assert!(GraphCache::read_from(&mut &truncated[..]).is_err());
+    }
+}
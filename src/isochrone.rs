@@ -0,0 +1,405 @@
+// Isochrones: the area reachable within a cost threshold, as polygons.
+//
+// The reached part of every edge (including the partial stretch of edges leaving the reachable set)
+// is rasterized onto a metric grid, grown by a few cells so the streets merge into areas, and the
+// outline of the filled cells is traced into polygons with holes. The result is written as GeoJSON.
+
+use crate::geo::EARTH_RADIUS_M;
+use crate::osm_loader::OsmGraph;
+use hashbrown::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// (lon, lat) pairs, closed (first == last), in GeoJSON order.
+pub type Ring = Vec<(f64, f64)>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polygon {
+    /// Counter-clockwise outline.
+    pub exterior: Ring,
+    /// Clockwise outlines of unreachable areas inside it.
+    pub holes: Vec<Ring>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Isochrone {
+    pub threshold: f64,
+    pub polygons: Vec<Polygon>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct IsochroneOptions {
+    /// Grid resolution in meters.
+    pub cell_size_m: f64,
+    /// Cells added around every reached cell, so parallel streets merge into one area.
+    pub buffer_cells: usize,
+}
+
+impl Default for IsochroneOptions {
+    fn default() -> Self {
+        Self {
+            cell_size_m: 50.0,
+            buffer_cells: 1,
+        }
+    }
+}
+
+/// Equirectangular projection around a reference latitude, in meters.
+struct Projection {
+    meters_per_deg_lat: f64,
+    meters_per_deg_lon: f64,
+}
+
+impl Projection {
+    fn new(ref_lat: f64) -> Self {
+        let meters_per_deg_lat = EARTH_RADIUS_M * std::f64::consts::PI / 180.0;
+        Self {
+            meters_per_deg_lat,
+            meters_per_deg_lon: meters_per_deg_lat * ref_lat.to_radians().cos(),
+        }
+    }
+
+    fn to_xy(&self, (lat, lon): (f64, f64)) -> (f64, f64) {
+        (lon * self.meters_per_deg_lon, lat * self.meters_per_deg_lat)
+    }
+
+    fn to_lon_lat(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (x / self.meters_per_deg_lon, y / self.meters_per_deg_lat)
+    }
+}
+
+/// One isochrone per threshold (in the graph's cost unit). `dist` holds the costs per OSM node,
+/// e.g. from `OsmGraph::collapse`.
+pub fn isochrones(
+    osm: &OsmGraph,
+    dist: &[f64],
+    thresholds: &[f64],
+    options: IsochroneOptions,
+) -> Vec<Isochrone> {
+    let reached: Vec<usize> = (0..osm.num_osm_nodes())
+        .filter(|&idx| dist[idx].is_finite())
+        .collect();
+    let ref_lat = reached.first().map_or(0.0, |&idx| osm.coords[idx].0);
+    let projection = Projection::new(ref_lat);
+    let cell = options.cell_size_m;
+
+    thresholds
+        .iter()
+        .map(|&threshold| {
+            let mut cells = HashSet::new();
+            let mut mark = |(x, y): (f64, f64)| {
+                cells.insert(((x / cell).floor() as i64, (y / cell).floor() as i64));
+            };
+            for u in 0..osm.graph.num_nodes() {
+                let a = osm.original(u);
+                if dist[a] > threshold {
+                    continue;
+                }
+                let start = projection.to_xy(osm.coords[a]);
+                mark(start);
+                for (v, weight) in osm.graph.neighbors(u) {
+                    let b = osm.original(v);
+                    // Share of the edge reached before the threshold runs out.
+                    let reach = if weight > 0.0 {
+                        ((threshold - dist[a]) / weight).min(1.0)
+                    } else {
+                        1.0
+                    };
+                    let end = projection.to_xy(osm.coords[b]);
+                    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+                    let length = (dx * dx + dy * dy).sqrt() * reach;
+                    let steps = (length / (cell / 2.0)).ceil() as usize;
+                    for step in 1..=steps {
+                        let f = reach * step as f64 / steps as f64;
+                        mark((start.0 + dx * f, start.1 + dy * f));
+                    }
+                }
+            }
+            let filled = dilate(&cells, options.buffer_cells as i64);
+            let polygons = trace_polygons(&filled)
+                .into_iter()
+                .map(|(exterior, holes)| {
+                    let to_ring = |ring: Vec<(i64, i64)>| {
+                        ring.into_iter()
+                            .map(|(i, j)| projection.to_lon_lat((i as f64 * cell, j as f64 * cell)))
+                            .collect()
+                    };
+                    Polygon {
+                        exterior: to_ring(exterior),
+                        holes: holes.into_iter().map(to_ring).collect(),
+                    }
+                })
+                .collect();
+            Isochrone {
+                threshold,
+                polygons,
+            }
+        })
+        .collect()
+}
+
+/// Closed ring of grid vertices.
+type GridRing = Vec<(i64, i64)>;
+
+fn dilate(cells: &HashSet<(i64, i64)>, radius: i64) -> HashSet<(i64, i64)> {
+    let mut grown = HashSet::with_capacity(cells.len() * (2 * radius as usize + 1).pow(2));
+    for &(i, j) in cells {
+        for di in -radius..=radius {
+            for dj in -radius..=radius {
+                grown.insert((i + di, j + dj));
+            }
+        }
+    }
+    grown
+}
+
+/// Twice the signed area of a closed ring; positive when counter-clockwise.
+fn doubled_area(ring: &[(i64, i64)]) -> i64 {
+    ring.windows(2)
+        .map(|w| w[0].0 * w[1].1 - w[1].0 * w[0].1)
+        .sum()
+}
+
+/// Whether the point lies inside the closed ring (even-odd rule).
+fn contains(ring: &[(i64, i64)], (px, py): (f64, f64)) -> bool {
+    let mut inside = false;
+    for w in ring.windows(2) {
+        let ((x0, y0), (x1, y1)) = (
+            (w[0].0 as f64, w[0].1 as f64),
+            (w[1].0 as f64, w[1].1 as f64),
+        );
+        if (y0 > py) != (y1 > py) && px < x0 + (py - y0) / (y1 - y0) * (x1 - x0) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Outlines of a set of filled grid cells, in grid-vertex coordinates (cell (i, j) spans
+/// [i, i + 1] x [j, j + 1]). Returns (exterior, holes) per connected area, rings closed and without
+/// collinear vertices. Cells that only touch at a corner become separate polygons.
+fn trace_polygons(filled: &HashSet<(i64, i64)>) -> Vec<(GridRing, Vec<GridRing>)> {
+    // Boundary edges, directed so the filled cell is on their left.
+    let mut outgoing: HashMap<(i64, i64), Vec<(i64, i64)>> = HashMap::new();
+    let mut add = |from: (i64, i64), to: (i64, i64)| outgoing.entry(from).or_default().push(to);
+    for &(i, j) in filled {
+        if !filled.contains(&(i, j - 1)) {
+            add((i, j), (i + 1, j));
+        }
+        if !filled.contains(&(i + 1, j)) {
+            add((i + 1, j), (i + 1, j + 1));
+        }
+        if !filled.contains(&(i, j + 1)) {
+            add((i + 1, j + 1), (i, j + 1));
+        }
+        if !filled.contains(&(i - 1, j)) {
+            add((i, j + 1), (i, j));
+        }
+    }
+
+    // Follow the edges into rings. Where two rings touch at a corner, turn left, which keeps
+    // diagonal neighbours apart.
+    let mut starts: Vec<(i64, i64)> = outgoing.keys().copied().collect();
+    starts.sort_unstable();
+    let mut rings = Vec::new();
+    for start in starts {
+        while let Some(first) = outgoing.get_mut(&start).and_then(|ends| ends.pop()) {
+            let mut ring = vec![start, first];
+            let mut cur = first;
+            while cur != start {
+                let prev = ring[ring.len() - 2];
+                let dir = (cur.0 - prev.0, cur.1 - prev.1);
+                let ends = outgoing
+                    .get_mut(&cur)
+                    .expect("boundary edges form closed rings");
+                let turn_rank = |end: &(i64, i64)| {
+                    let next = (end.0 - cur.0, end.1 - cur.1);
+                    let cross = dir.0 * next.1 - dir.1 * next.0;
+                    // Left, then straight, then right.
+                    -cross.signum()
+                };
+                let pick = (0..ends.len())
+                    .min_by_key(|&k| turn_rank(&ends[k]))
+                    .expect("boundary edges form closed rings");
+                let next = ends.swap_remove(pick);
+                ring.push(next);
+                cur = next;
+            }
+            rings.push(simplify(ring));
+        }
+    }
+
+    let (exteriors, holes): (Vec<_>, Vec<_>) =
+        rings.into_iter().partition(|ring| doubled_area(ring) > 0);
+    let mut polygons: Vec<_> = exteriors.into_iter().map(|e| (e, Vec::new())).collect();
+    for hole in holes {
+        // A point just inside the filled cell left of the hole's first edge.
+        let (a, b) = (hole[0], hole[1]);
+        let probe = (
+            (a.0 + b.0) as f64 / 2.0 - (b.1 - a.1) as f64 * 0.25,
+            (a.1 + b.1) as f64 / 2.0 + (b.0 - a.0) as f64 * 0.25,
+        );
+        let owner = polygons
+            .iter()
+            .enumerate()
+            .filter(|(_, (exterior, _))| contains(exterior, probe))
+            .min_by_key(|(_, (exterior, _))| doubled_area(exterior))
+            .map(|(k, _)| k);
+        if let Some(k) = owner {
+            polygons[k].1.push(hole);
+        }
+    }
+    polygons
+}
+
+/// Drops vertices in the middle of straight runs. Keeps the ring closed.
+fn simplify(ring: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
+    let open = &ring[..ring.len() - 1];
+    let n = open.len();
+    let mut kept: Vec<(i64, i64)> = (0..n)
+        .filter(|&k| {
+            let (p, c, q) = (open[(k + n - 1) % n], open[k], open[(k + 1) % n]);
+            (c.0 - p.0) * (q.1 - c.1) != (c.1 - p.1) * (q.0 - c.0)
+        })
+        .map(|k| open[k])
+        .collect();
+    kept.push(kept[0]);
+    kept
+}
+
+/// Isochrones as a GeoJSON FeatureCollection: one MultiPolygon feature per threshold, with the
+/// threshold and its unit as properties.
+pub fn to_geojson(isochrones: &[Isochrone], unit: &str) -> String {
+    let ring_json = |ring: &Ring| {
+        let points: Vec<String> = ring
+            .iter()
+            .map(|(lon, lat)| format!("[{:.7},{:.7}]", lon, lat))
+            .collect();
+        format!("[{}]", points.join(","))
+    };
+    let mut out = String::from("{\"type\":\"FeatureCollection\",\"features\":[");
+    for (k, isochrone) in isochrones.iter().enumerate() {
+        if k > 0 {
+            out.push(',');
+        }
+        let polygons: Vec<String> = isochrone
+            .polygons
+            .iter()
+            .map(|polygon| {
+                let rings: Vec<String> = std::iter::once(&polygon.exterior)
+                    .chain(&polygon.holes)
+                    .map(ring_json)
+                    .collect();
+                format!("[{}]", rings.join(","))
+            })
+            .collect();
+        write!(
+            out,
+            "{{\"type\":\"Feature\",\"properties\":{{\"threshold\":{},\"unit\":\"{}\"}},\"geometry\":{{\"type\":\"MultiPolygon\",\"coordinates\":[{}]}}}}",
+            isochrone.threshold,
+            unit,
+            polygons.join(",")
+        )
+        .expect("writing to a String");
+    }
+    out.push_str("]}");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(grid: &[&str]) -> HashSet<(i64, i64)> {
+        // Rows top to bottom, so flip j.
+        let mut filled = HashSet::new();
+        for (row, line) in grid.iter().enumerate() {
+            for (i, c) in line.chars().enumerate() {
+                if c == '#' {
+                    filled.insert((i as i64, (grid.len() - 1 - row) as i64));
+                }
+            }
+        }
+        filled
+    }
+
+    #[test]
+    fn traces_holes_and_separate_areas() {
+        let filled = cells(&[
+            "###   ", //
+            "# #   ", //
+            "###  #", //
+            "    # ", //
+        ]);
+        let mut polygons = trace_polygons(&filled);
+        polygons.sort_by_key(|(exterior, _)| -doubled_area(exterior));
+        assert_eq!(polygons.len(), 3);
+
+        let (ring, holes) = &polygons[0];
+        assert_eq!(ring.len(), 5);
+        assert_eq!(doubled_area(ring), 2 * 9);
+        assert_eq!(holes.len(), 1);
+        assert_eq!(doubled_area(&holes[0]), -2);
+        assert_eq!(holes[0].first(), holes[0].last());
+
+        // The two cells touching at a corner stay apart.
+        assert_eq!(doubled_area(&polygons[1].0), 2);
+        assert_eq!(doubled_area(&polygons[2].0), 2);
+    }
+
+    #[test]
+    fn partial_edges_are_cut_at_the_threshold() {
+        use crate::geo::haversine_meters;
+        use crate::graph::Graph;
+        use crate::osm_loader::LoadOptions;
+        use osmpbfreader::NodeId;
+
+        // Three nodes along the equator, 0.01 degrees apart.
+        let coords = vec![(0.0, 0.0), (0.0, 0.01), (0.0, 0.02)];
+        let leg = haversine_meters(0.0, 0.0, 0.0, 0.01);
+        let edges = [(0, 1, leg), (1, 0, leg), (1, 2, leg), (2, 1, leg)];
+        let osm = OsmGraph {
+            graph: Graph::from_edges(3, &edges),
+            idx_to_id: (0..3).map(NodeId).collect(),
+            id_to_idx: (0..3).map(|i| (NodeId(i), i as usize)).collect(),
+            coords,
+            num_ways: 1,
+            options: LoadOptions::default(),
+            copy_of: Vec::new(),
+            restrictions: Default::default(),
+        };
+        let dist = [0.0, leg, 2.0 * leg];
+        let options = IsochroneOptions::default();
+        let result = isochrones(&osm, &dist, &[leg / 2.0, 10.0 * leg], options);
+
+        let east_edge = |iso: &Isochrone| {
+            assert_eq!(iso.polygons.len(), 1);
+            assert!(iso.polygons[0].holes.is_empty());
+            iso.polygons[0]
+                .exterior
+                .iter()
+                .map(|&(lon, _)| lon)
+                .fold(f64::NEG_INFINITY, f64::max)
+        };
+        // Halfway along the first edge, plus at most the buffer and one cell of rounding.
+        let slack = 0.01 * 2.0 * options.cell_size_m / leg;
+        let half = east_edge(&result[0]);
+        assert!(half > 0.005 && half < 0.005 + slack, "{}", half);
+        let all = east_edge(&result[1]);
+        assert!(all > 0.02 && all < 0.02 + slack, "{}", all);
+    }
+
+    #[test]
+    fn geojson_output() {
+        let isochrone = Isochrone {
+            threshold: 300.0,
+            polygons: vec![Polygon {
+                exterior: vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 0.0)],
+                holes: vec![],
+            }],
+        };
+        assert_eq!(
+            to_geojson(&[isochrone], "s"),
+            "{\"type\":\"FeatureCollection\",\"features\":[{\"type\":\"Feature\",\"properties\":{\"threshold\":300,\"unit\":\"s\"},\"geometry\":{\"type\":\"MultiPolygon\",\"coordinates\":[[[[0.0000000,0.0000000],[1.0000000,0.0000000],[1.0000000,1.0000000],[0.0000000,0.0000000]]]]}}]}"
+        );
+    }
+}
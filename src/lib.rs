@@ -0,0 +1,38 @@
+// SSP algorithms (BMSSP and Dijkstra) over road graphs built from OSM extracts or edge-list CSVs.
+
+pub mod algorithm;
+pub mod astar;
+pub mod bidirectional;
+mod binary_io;
+pub mod block_data_structure;
+pub mod block_list;
+pub mod bmssp;
+pub mod ch;
+pub mod csv_loader;
+#[cfg(test)]
+mod differential_tests;
+pub mod dijkstra;
+pub mod geo;
+pub mod graph;
+pub mod graph_cache;
+pub mod isochrone;
+pub mod matrix;
+pub mod osm_loader;
+pub mod pq_block_list;
+pub mod profile;
+pub mod query_context;
+pub mod shortest_path_tree;
+pub mod spatial_index;
+#[cfg(test)]
+mod test_util;
+pub mod tree_block_list;
+pub mod tune;
+pub mod turn_restrictions;
+pub mod verify;
+pub mod weighting;
+
+pub use algorithm::{Algorithm, Bmssp, Dijkstra, ShortestPathAlgorithm};
+pub use bmssp::BmsspParams;
+pub use graph::Graph;
+pub use query_context::QueryContext;
+pub use shortest_path_tree::{MultiSourceTree, Predecessor, ShortestPathTree};
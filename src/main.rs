@@ -1,34 +1,89 @@
-use anyhow::{Context, Result};
-use clap::Parser;
+use anyhow::{bail, Context, Result};
+use clap::{ArgGroup, Parser};
 use csv::Writer;
-use fnv::FnvHashMap;
-use osmpbfreader::{NodeId, OsmObj, OsmPbfReader, Tags, WayId};
+use osmpbfreader::NodeId;
+use ssps::block_list::BlockListKind;
+use ssps::ch::ContractionHierarchy;
+use ssps::graph_cache::GraphCache;
+use ssps::isochrone::{self, IsochroneOptions};
+use ssps::osm_loader::{self, LoadOptions, OsmGraph};
+use ssps::profile::Profile;
+use ssps::spatial_index::SpatialIndex;
+use ssps::verify::Comparison;
+use ssps::weighting::Weighting;
+use ssps::{
+    astar, bidirectional, bmssp, dijkstra, Algorithm, Bmssp, BmsspParams, ShortestPathTree,
+};
 use std::collections::HashSet;
-use std::fs::File;
-
-mod block_data_structure;
-mod bmssp;
-mod dijkstra;
-mod geo;
-mod pq_block_list;
-mod tree_block_list;
 
 #[derive(Parser, Debug)]
 #[command(name = "ssps")]
 #[command(about = "Build a graph from an OSM .pbf and run an SSP algorithm from a source node id.", long_about = None)]
+#[command(group(ArgGroup::new("input").required(true).multiple(true).args(["pbf", "graph"])))]
+#[command(group(ArgGroup::new("source_node").required(true).args(["source", "source_index", "source_lat"])))]
+#[command(group(ArgGroup::new("target_node").args(["target", "target_index", "target_lat"])))]
 struct Cli {
-    /// Path to the .osm.pbf file
+    /// Path to the .osm.pbf file. With --graph, only used to check the cache is up to date.
+    #[arg(short, long)]
+    pbf: Option<String>,
+
+    /// Graph cache written by build-graph; skips parsing the .pbf.
     #[arg(short, long)]
-    pbf: String,
+    graph: Option<String>,
 
-    /// Source node id to run SSP from
+    /// Source OSM node id to run SSP from
     #[arg(short, long)]
-    source: i64,
+    source: Option<i64>,
+
+    /// Source as a raw graph index instead of an OSM node id. Indices are not stable across runs.
+    #[arg(long)]
+    source_index: Option<usize>,
+
+    /// Source latitude; the source snaps to the nearest routable node.
+    #[arg(long, requires = "source_lon", allow_negative_numbers = true)]
+    source_lat: Option<f64>,
+
+    /// Source longitude, used with --source-lat.
+    #[arg(long, requires = "source_lat", allow_negative_numbers = true)]
+    source_lon: Option<f64>,
+
+    /// Target OSM node id. Stops the search as soon as the target is settled and reports the route
+    /// (the output CSV then lists the nodes along the route).
+    #[arg(short, long)]
+    target: Option<i64>,
+
+    /// Target as a raw graph index instead of an OSM node id.
+    #[arg(long)]
+    target_index: Option<usize>,
+
+    /// Target latitude; the target snaps to the nearest routable node.
+    #[arg(long, requires = "target_lon", allow_negative_numbers = true)]
+    target_lat: Option<f64>,
+
+    /// Target longitude, used with --target-lat.
+    #[arg(long, requires = "target_lat", allow_negative_numbers = true)]
+    target_lon: Option<f64>,
 
-    #[arg(short, long, default_value_t = String::from("bmssp"))]
-    algorithm: String,
+    #[arg(short, long, default_value_t = Algorithm::Bmssp)]
+    algorithm: Algorithm,
 
-    /// Output CSV (node_id, distance_m). If omitted, prints a summary to stdout.
+    /// Block list backend BMSSP runs on (paper, tree or pq), to benchmark them against each other.
+    #[arg(long, default_value_t = BlockListKind::Tree)]
+    block_list: BlockListKind,
+
+    /// BMSSP's k (see BmsspParams), instead of floor(log^(1/3) N).
+    #[arg(long)]
+    k: Option<usize>,
+
+    /// BMSSP's t, instead of floor(log^(2/3) N).
+    #[arg(long)]
+    t: Option<usize>,
+
+    /// BMSSP's top level l, instead of ceil(log N / t).
+    #[arg(long)]
+    l: Option<usize>,
+
+    /// Output CSV (node_id, distance_m or time_s). If omitted, prints a summary to stdout.
     #[arg(short, long)]
     out: Option<String>,
 
@@ -36,187 +91,431 @@ struct Cli {
     #[arg(long, default_value_t = false)]
     include_unreachable: bool,
 
+    /// Add a parent_id column (predecessor on the shortest path) to the output CSV
+    #[arg(long, default_value_t = false)]
+    include_parents: bool,
+
     /// Only include 'highway' ways (recommended). If false, attempts to include all linear ways.
     #[arg(long, default_value_t = true)]
     only_highways: bool,
-}
 
-#[derive(Clone, Debug)]
-struct WayLite {
-    id: WayId,
-    nodes: Vec<NodeId>,
-    tags: Tags,
-}
+    /// Don't apply turn restrictions (type=restriction relations) when building the graph.
+    #[arg(long, default_value_t = false)]
+    ignore_turn_restrictions: bool,
 
-fn is_way_routable(tags: &Tags, only_highways: bool) -> bool {
-    if only_highways && !tags.contains_key("highway") {
-        return false;
-    }
-    // Exclude areas and non-linear ways
-    if tags.get("area").map(|v| v == "yes").unwrap_or(false) {
-        return false;
-    }
-    true
+    /// Edge weights: segment length in meters (default), or travel time in seconds from the
+    /// highway class and maxspeed. With --graph, must match what the cache was built with.
+    #[arg(short, long)]
+    weight: Option<Weighting>,
+
+    /// Mode of travel (car, bike, foot) deciding which ways are usable, in which direction and, with
+    /// --weight time, how fast. Without one, every highway is used in the directions cars may take.
+    /// With --graph, must match what the cache was built with.
+    #[arg(long)]
+    profile: Option<Profile>,
+
+    /// With a target, direct the search towards it using the straight-line distance as a lower
+    /// bound: A* for dijkstra, and an experimental potential-reduced BMSSP for bmssp.
+    #[arg(long, requires = "target_node")]
+    goal_directed: bool,
+
+    /// With a target, search from both ends at once (the reversed graph is built after loading).
+    /// BMSSP's bidirectional variant is experimental.
+    #[arg(long, requires = "target_node", conflicts_with = "goal_directed")]
+    bidirectional: bool,
+
+    /// Contraction hierarchy written by `build-graph --ch` for the same graph; answers target
+    /// queries without searching the whole graph.
+    #[arg(long, requires = "target_node", conflicts_with_all = ["bidirectional", "goal_directed"])]
+    ch: Option<String>,
+
+    /// Also run Dijkstra from the source and compare every node's distance with the chosen
+    /// algorithm's. Reports the mismatches and fails if there are any; writes no output.
+    #[arg(long, conflicts_with_all = ["target_node", "max_distance", "isochrones", "out"])]
+    verify: bool,
+
+    /// Only search nodes closer than this to the source, in the cost unit (meters, or seconds with
+    /// --weight time). Nodes at or beyond it are left out of the output, as if unreachable.
+    #[arg(long, conflicts_with_all = ["target_node", "include_parents"])]
+    max_distance: Option<f64>,
+
+    /// Cost thresholds for isochrones, comma-separated and in the cost unit (meters, or seconds with
+    /// --weight time), e.g. 300,600,900. Writes the reachable areas to --geojson.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        requires = "geojson",
+        conflicts_with = "target_node"
+    )]
+    isochrones: Vec<f64>,
+
+    /// Output GeoJSON file for --isochrones.
+    #[arg(long, requires = "isochrones")]
+    geojson: Option<String>,
+
+    /// Grid cell size in meters used to build the isochrone polygons.
+    #[arg(long, default_value_t = 50.0)]
+    cell_size: f64,
 }
 
-fn is_oneway(tags: &Tags) -> Option<i8> {
-    if let Some(v) = tags.get("oneway") {
-        match v.as_str() {
-            "yes" | "true" | "1" => return Some(1),
-            "-1" => return Some(-1),
-            _ => {}
+/// Maps a node given either as an OSM id or as a raw graph index to its graph index.
+fn resolve_node(
+    role: &str,
+    osm_id: Option<i64>,
+    index: Option<usize>,
+    osm: &OsmGraph,
+    pbf: Option<&str>,
+) -> Result<Option<usize>> {
+    let num_nodes = osm.num_osm_nodes();
+    if let Some(index) = index {
+        if index >= num_nodes {
+            bail!(
+                "{} index {} is out of range (graph has {} nodes)",
+                role,
+                index,
+                num_nodes
+            );
         }
+        return Ok(Some(index));
+    }
+    let Some(osm_id) = osm_id else {
+        return Ok(None);
+    };
+    if let Some(idx) = osm.node_index(NodeId(osm_id)) {
+        return Ok(Some(idx));
     }
-    if tags
-        .get("junction")
-        .map(|v| v == "roundabout")
-        .unwrap_or(false)
-    {
-        return Some(1);
+    let Some(pbf) = pbf else {
+        bail!("{} node {} is not in the graph", role, osm_id)
+    };
+    // Rescan the extract so a node that exists but isn't on a routable way gets its own error.
+    let probe = HashSet::from([NodeId(osm_id)]);
+    if osm_loader::find_nodes(pbf, &probe)?.is_empty() {
+        bail!("{} node {} was not found in the extract", role, osm_id)
     }
-    None
+    bail!("{} node {} is not part of any routable way", role, osm_id)
 }
 
-enum SspAlgorithm {
-    Bmssp,
-    Dijkstra,
+/// Snaps a coordinate to the nearest routable node and reports how far it moved.
+fn snap_node(
+    role: &str,
+    lat: f64,
+    lon: f64,
+    index: &SpatialIndex,
+    idx_to_id: &[NodeId],
+) -> Result<usize> {
+    let (idx, moved) = index
+        .nearest(lat, lon)
+        .context("graph has no routable nodes to snap to")?;
+    println!(
+        "{} ({:.6}, {:.6}) snapped to node {} ({:.1} m away)",
+        role, lat, lon, idx_to_id[idx].0, moved
+    );
+    Ok(idx)
 }
 
-impl SspAlgorithm {
-    fn from(string: &str) -> Self {
-        match string {
-            "bmssp" => SspAlgorithm::Bmssp{},
-            "dijkstra" => SspAlgorithm::Dijkstra{},
-            _ => panic!("Algorithm not found for input string: {}, possible options are: (\"bmssp\", \"dijkstra\")", string),
-        }
-    }
-
-    fn run(&self, neighbors: &Vec<Vec<(usize, f64)>>, start: usize) -> Vec<f64> {
-        match self {
-            SspAlgorithm::Bmssp => bmssp::bmssp_all(neighbors, start),
-            SspAlgorithm::Dijkstra => dijkstra::dijkstra_all(neighbors, start),
-        }
+fn write_route(
+    out_path: &str,
+    route: &[(usize, f64)],
+    idx_to_id: &[NodeId],
+    weighting: Weighting,
+) -> Result<()> {
+    let mut wtr =
+        Writer::from_path(out_path).with_context(|| format!("creating CSV {}", out_path))?;
+    wtr.write_record(["node_id", weighting.column()])?;
+    for &(idx, dist) in route {
+        wtr.write_record(&[idx_to_id[idx].0.to_string(), format!("{:.6}", dist)])?;
     }
+    wtr.flush()?;
+    Ok(())
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    // Set Algorithm.
-    let ssp = SspAlgorithm::from(&cli.algorithm);
-
-    // Pass 1: collect routable ways and the set of node ids they reference
-    let file = File::open(&cli.pbf).with_context(|| format!("opening {}", &cli.pbf))?;
-    let mut pbf = OsmPbfReader::new(file);
-
-    let mut needed_nodes: HashSet<NodeId> = HashSet::new();
-    let mut ways: Vec<WayLite> = Vec::new();
-
-    for obj in pbf.iter() {
-        let obj = obj?;
-        if let OsmObj::Way(w) = obj {
-            if is_way_routable(&w.tags, cli.only_highways) {
-                for nid in &w.nodes {
-                    needed_nodes.insert(*nid);
-                }
-                ways.push(WayLite {
-                    id: w.id,
-                    nodes: w.nodes.clone(),
-                    tags: w.tags.clone(),
-                });
+    let osm = match (&cli.graph, &cli.pbf) {
+        (Some(graph_path), pbf) => {
+            let cache = GraphCache::read(graph_path)?;
+            if let Some(pbf) = pbf {
+                cache.check_source(pbf)?;
             }
+            cache.check_options(
+                graph_path,
+                cli.weight,
+                cli.profile,
+                cli.ignore_turn_restrictions,
+            )?;
+            println!("Loaded graph cache {}", graph_path);
+            cache.into_osm_graph()?
         }
-    }
+        (None, Some(pbf)) => {
+            let options = LoadOptions {
+                only_highways: cli.only_highways,
+                weighting: cli.weight.unwrap_or_default(),
+                profile: cli.profile,
+                turn_restrictions: !cli.ignore_turn_restrictions,
+            };
+            let osm = osm_loader::load_pbf(pbf, options)?;
+            println!(
+                "Collected {} routable ways; loaded coordinates for {} nodes",
+                osm.num_ways,
+                osm.num_osm_nodes()
+            );
+            osm
+        }
+        (None, None) => unreachable!("clap requires --pbf or --graph"),
+    };
+    let OsmGraph {
+        graph, idx_to_id, ..
+    } = &osm;
+    let weighting = osm.options.weighting;
 
     println!(
-        "Collected {} routable ways; {} unique node refs",
-        ways.len(),
-        needed_nodes.len()
+        "Graph: {} nodes, {} directed edges",
+        graph.num_nodes(),
+        graph.num_edges()
     );
 
-    // Pass 2: read coordinates for needed nodes
-    let file2 = File::open(&cli.pbf).with_context(|| format!("reopening {}", &cli.pbf))?;
-    let mut pbf2 = OsmPbfReader::new(file2);
-
-    let mut coords: FnvHashMap<NodeId, (f64, f64)> = FnvHashMap::default();
-    for obj in pbf2.iter() {
-        let obj = obj?;
-        if let OsmObj::Node(n) = obj {
-            if needed_nodes.contains(&n.id) {
-                coords.insert(n.id, (n.lat(), n.lon()));
-            }
+    // Set Algorithm.
+    let params = match (cli.k, cli.t, cli.l) {
+        (None, None, None) => None,
+        (k, t, l) => Some(BmsspParams::with_overrides(graph.num_nodes(), k, t, l)?),
+    };
+    let mut bmssp = Bmssp::new(cli.block_list);
+    if let Some(params) = params {
+        bmssp = bmssp.with_params(params, graph.num_nodes())?;
+    }
+    let ssp = cli.algorithm.solver_with(bmssp);
+    let reverse = cli.bidirectional.then(|| graph.reverse());
+    let ch = match &cli.ch {
+        Some(ch_path) => {
+            let ch = ContractionHierarchy::read(ch_path)?;
+            ch.check_graph(graph)
+                .with_context(|| format!("loading {}", ch_path))?;
+            Some(ch)
         }
+        None => None,
+    };
+    let restrictions = osm.restrictions;
+    if osm.options.turn_restrictions {
+        println!(
+            "Turn restrictions: {} applied, {} skipped (via way), {} skipped (invalid or not routable)",
+            restrictions.applied, restrictions.via_way, restrictions.invalid
+        );
     }
 
-    println!(
-        "Loaded coordinates for {} nodes actually present",
-        coords.len()
-    );
+    let snap_index = if cli.source_lat.is_some() || cli.target_lat.is_some() {
+        Some(osm.spatial_index())
+    } else {
+        None
+    };
 
-    // Build index mapping and adjacency
-    let mut id_to_idx: FnvHashMap<NodeId, usize> = FnvHashMap::default();
-    let mut idx_to_id: Vec<NodeId> = Vec::with_capacity(coords.len());
+    // Source (and target) mapping
+    let src_idx = match resolve_node(
+        "Source",
+        cli.source,
+        cli.source_index,
+        &osm,
+        cli.pbf.as_deref(),
+    )? {
+        Some(idx) => idx,
+        None => snap_node(
+            "Source",
+            cli.source_lat.expect("clap requires a source"),
+            cli.source_lon.expect("clap requires --source-lon"),
+            snap_index.as_ref().unwrap(),
+            idx_to_id,
+        )?,
+    };
+    let dst_idx = match resolve_node(
+        "Target",
+        cli.target,
+        cli.target_index,
+        &osm,
+        cli.pbf.as_deref(),
+    )? {
+        Some(idx) => Some(idx),
+        None => match (cli.target_lat, cli.target_lon) {
+            (Some(lat), Some(lon)) => Some(snap_node(
+                "Target",
+                lat,
+                lon,
+                snap_index.as_ref().unwrap(),
+                idx_to_id,
+            )?),
+            _ => None,
+        },
+    };
+    let source = idx_to_id[src_idx].0;
+    println!("Source: node {} (index {})", source, src_idx);
 
-    for (&nid, _) in coords.iter() {
-        let idx = idx_to_id.len();
-        idx_to_id.push(nid);
-        id_to_idx.insert(nid, idx);
+    if cli.verify {
+        let actual = osm.collapse(&ssp.distances(graph, src_idx));
+        let expected = osm.collapse(&dijkstra::dijkstra_all(graph, src_idx));
+        let comparison = Comparison::new(&expected, &actual);
+        println!("{} vs dijkstra: {}", ssp.name(), comparison);
+        for &(idx, e, a) in &comparison.examples {
+            println!(
+                "  node {}: dijkstra {:.6}, {} {:.6}",
+                idx_to_id[idx].0,
+                e,
+                ssp.name(),
+                a
+            );
+        }
+        if !comparison.is_match() {
+            bail!(
+                "{} disagrees with dijkstra on {} nodes",
+                ssp.name(),
+                comparison.mismatches
+            );
+        }
+        return Ok(());
     }
 
-    let mut adj: Vec<Vec<(usize, f64)>> = vec![Vec::new(); idx_to_id.len()];
-
-    let mut edges_added: usize = 0;
-    for w in &ways {
-        if w.nodes.len() < 2 {
-            continue;
-        }
-        let oneway = is_oneway(&w.tags);
-        for pair in w.nodes.windows(2) {
-            let (a, b) = (pair[0], pair[1]);
-            let (&(alat, alon), &(blat, blon)) = match (coords.get(&a), coords.get(&b)) {
-                (Some(ca), Some(cb)) => (ca, cb),
-                _ => continue,
-            };
-            let weight = geo::haversine_meters(alat, alon, blat, blon);
-            if weight.is_finite() && weight > 0.0 {
-                if let (Some(&u), Some(&v)) = (id_to_idx.get(&a), id_to_idx.get(&b)) {
-                    match oneway {
-                        Some(1) => {
-                            adj[u].push((v, weight));
-                            edges_added += 1;
-                        }
-                        Some(-1) => {
-                            adj[v].push((u, weight));
-                            edges_added += 1;
-                        }
-                        None => {
-                            adj[u].push((v, weight));
-                            adj[v].push((u, weight));
-                            edges_added += 2;
+    use std::time::SystemTime;
+    if let Some(dst_idx) = dst_idx {
+        let target = idx_to_id[dst_idx].0;
+        let now = SystemTime::now();
+        let route = if let Some(ch) = &ch {
+            // Route to whichever copy of a via node is cheapest to arrive at.
+            let best = osm
+                .arrivals(dst_idx)
+                .filter_map(|arrival| ch.query(src_idx, arrival))
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+            if let Ok(elapsed) = now.elapsed() {
+                println!("{} s", elapsed.as_secs_f64());
+            }
+            best.map(|(_, edges)| {
+                let mut cost = 0.0;
+                let mut route = vec![(src_idx, 0.0)];
+                for edge in edges {
+                    let (head, weight) = graph.edge(edge.edge_idx);
+                    cost += weight;
+                    route.push((osm.original(head), cost));
+                }
+                route
+            })
+        } else {
+            let search_to = |arrival: usize| {
+                if let Some(reverse) = &reverse {
+                    match cli.algorithm {
+                        Algorithm::Dijkstra => {
+                            bidirectional::dijkstra_bidirectional(graph, reverse, src_idx, arrival)
                         }
-                        _ => {}
+                        Algorithm::Bmssp => bidirectional::bmssp_bidirectional(
+                            graph, reverse, src_idx, arrival, &bmssp,
+                        ),
+                    }
+                } else if cli.goal_directed {
+                    let heuristic = osm.heuristic_to(arrival);
+                    match cli.algorithm {
+                        Algorithm::Dijkstra => astar::astar(graph, src_idx, arrival, heuristic),
+                        Algorithm::Bmssp => bmssp::bmssp_to_with_potential(
+                            graph, src_idx, arrival, heuristic, &bmssp,
+                        ),
                     }
+                } else {
+                    ssp.tree_to(graph, src_idx, arrival)
+                }
+            };
+            // A via node is reached through one of its copies, so search to each of them, as the
+            // CH path does, and keep the tree that arrives cheapest.
+            let arrivals: Vec<usize> = osm.arrivals(dst_idx).collect();
+            if arrivals.len() > 1 {
+                println!(
+                    "Target is split by turn restrictions: one search per arrival ({})",
+                    arrivals.len()
+                );
+            }
+            let mut num_labelled = 0;
+            let mut best: Option<(f64, ShortestPathTree)> = None;
+            for arrival in arrivals {
+                let tree = search_to(arrival);
+                num_labelled += tree.dist.iter().filter(|d| d.is_finite()).count();
+                if best
+                    .as_ref()
+                    .is_none_or(|(cost, _)| tree.dist[arrival] < *cost)
+                {
+                    best = Some((tree.dist[arrival], tree));
                 }
             }
+            let (_, tree) = best.expect("a node always arrives at itself");
+            if let Ok(elapsed) = now.elapsed() {
+                println!("{} s", elapsed.as_secs_f64());
+            }
+            println!("Labelled {} nodes", num_labelled);
+            osm.route_to(&tree, dst_idx)
+        };
+        match route {
+            Some(route) => {
+                println!(
+                    "Cost from {} to {} ({}): {:.2}",
+                    source,
+                    target,
+                    weighting.unit(),
+                    route.last().map_or(0.0, |&(_, d)| d)
+                );
+                println!("Route: {} nodes", route.len());
+                if let Some(out_path) = &cli.out {
+                    write_route(out_path, &route, idx_to_id, weighting)?;
+                    println!("Wrote route to {}", out_path);
+                }
+            }
+            None => println!("{} is unreachable from {}", target, source),
         }
+        return Ok(());
     }
 
-    println!("Graph: {} nodes, {} directed edges", adj.len(), edges_added);
-
-    // Source mapping
-    let src_idx = cli.source as usize;
-
-    use std::time::SystemTime;
     let now = SystemTime::now();
-    let dist = ssp.run(&adj, src_idx);
+    let (dist, pred) = if let Some(max_cost) = cli.max_distance {
+        let mut dist = vec![f64::INFINITY; graph.num_nodes()];
+        for (idx, d) in ssp.within(graph, src_idx, max_cost) {
+            dist[idx] = d;
+        }
+        (osm.collapse(&dist), None)
+    } else if cli.include_parents {
+        let tree = osm.collapse_tree(&ssp.tree(graph, src_idx));
+        (tree.dist, Some(tree.pred))
+    } else {
+        (osm.collapse(&ssp.distances(graph, src_idx)), None)
+    };
     if let Ok(elapsed) = now.elapsed() {
         println!("{} s", elapsed.as_secs_f64());
     }
 
+    if let Some(geojson_path) = &cli.geojson {
+        if cli.cell_size <= 0.0 {
+            bail!("--cell-size must be positive, got {}", cli.cell_size);
+        }
+        let options = IsochroneOptions {
+            cell_size_m: cli.cell_size,
+            ..IsochroneOptions::default()
+        };
+        let isochrones = isochrone::isochrones(&osm, &dist, &cli.isochrones, options);
+        for iso in &isochrones {
+            println!(
+                "Isochrone {} {}: {} polygons",
+                iso.threshold,
+                weighting.unit(),
+                iso.polygons.len()
+            );
+        }
+        std::fs::write(
+            geojson_path,
+            isochrone::to_geojson(&isochrones, weighting.unit()),
+        )
+        .with_context(|| format!("writing GeoJSON {}", geojson_path))?;
+        println!("Wrote isochrones to {}", geojson_path);
+    }
+
     if let Some(out_path) = cli.out {
         let mut wtr =
             Writer::from_path(&out_path).with_context(|| format!("creating CSV {}", &out_path))?;
-        wtr.write_record(["node_id", "distance_m"])?;
+        if pred.is_some() {
+            wtr.write_record(["node_id", weighting.column(), "parent_id"])?;
+        } else {
+            wtr.write_record(["node_id", weighting.column()])?;
+        }
         let mut dist_with_idx: Vec<(usize, &f64)> = dist.iter().enumerate().collect();
         dist_with_idx.sort_by(|a, b| a.1.partial_cmp(b.1).unwrap());
         for (idx, d) in &dist_with_idx {
@@ -227,7 +526,15 @@ fn main() -> Result<()> {
                 } else {
                     String::from("inf")
                 };
-                wtr.write_record(&[nid.to_string(), val])?;
+                match &pred {
+                    Some(pred) => {
+                        let parent = pred[*idx]
+                            .map(|p| idx_to_id[p.node_id].0.to_string())
+                            .unwrap_or_default();
+                        wtr.write_record(&[nid.to_string(), val, parent])?;
+                    }
+                    None => wtr.write_record(&[nid.to_string(), val])?,
+                }
             }
         }
         wtr.flush()?;
@@ -239,7 +546,7 @@ fn main() -> Result<()> {
     } else {
         let reachable = dist.iter().filter(|x| x.is_finite()).count();
         println!("Nodes: {}", dist.len());
-        println!("Reachable from {}: {}", cli.source, reachable);
+        println!("Reachable from {}: {}", source, reachable);
         if reachable > 0 {
             let mut maxd = 0.0_f64;
             for d in dist.iter().copied().filter(|x| x.is_finite()) {
@@ -247,7 +554,7 @@ fn main() -> Result<()> {
                     maxd = d;
                 }
             }
-            println!("Max finite distance (m): {:.2}", maxd);
+            println!("Max finite cost ({}): {:.2}", weighting.unit(), maxd);
         }
     }
 
@@ -0,0 +1,245 @@
+// Many-to-many distance tables (e.g. depots x stops), computed with one single-source search per
+// source, spread over threads, or with a contraction hierarchy's buckets (`ch::many_to_many`).
+//
+// Binary layout (little-endian): magic, format version, the source ids, the target ids, then the
+// distances row by row (one row per source), `f64::INFINITY` where a target is unreachable.
+
+use crate::algorithm::ShortestPathAlgorithm;
+use crate::binary_io::*;
+use crate::graph::Graph;
+use crate::query_context::QueryContext;
+use anyhow::{ensure, Context, Result};
+use csv::Writer;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+const MAGIC: &[u8; 8] = b"SSPSMTRX";
+
+/// Bumped whenever the layout changes; files with another version are rejected.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Output format selector for the command line (`--format csv|binary`).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MatrixFormat {
+    /// One row per source: its id, then the cost to every target.
+    #[default]
+    Csv,
+    /// The binary layout described above, for tables too big for CSV.
+    Binary,
+}
+
+impl FromStr for MatrixFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(MatrixFormat::Csv),
+            "binary" => Ok(MatrixFormat::Binary),
+            _ => Err(format!(
+                "Format not found for input string: {}, possible options are: (\"csv\", \"binary\")",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MatrixFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MatrixFormat::Csv => "csv",
+            MatrixFormat::Binary => "binary",
+        })
+    }
+}
+
+/// Distances from every source to every target, one row per source, with the node ids that label
+/// the rows and columns (OSM ids, or graph indices for CSV graphs).
+#[derive(Clone, Debug, PartialEq)]
+pub struct DistanceMatrix {
+    pub source_ids: Vec<i64>,
+    pub target_ids: Vec<i64>,
+    pub dist: Vec<Vec<f64>>,
+}
+
+/// Runs `ssp` from every source and keeps the distances to `targets`, one row per source. Sources
+/// are handed out to `threads` workers one at a time, so uneven searches still balance out. Each
+/// worker reuses one QueryContext.
+pub fn distance_table(
+    graph: &Graph,
+    ssp: &(dyn ShortestPathAlgorithm + Sync),
+    sources: &[usize],
+    targets: &[usize],
+    threads: usize,
+) -> Vec<Vec<f64>> {
+    let next = AtomicUsize::new(0);
+    let rows = Mutex::new(vec![Vec::new(); sources.len()]);
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, sources.len().max(1)) {
+            scope.spawn(|| {
+                let mut ctx = QueryContext::new();
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(&source) = sources.get(i) else {
+                        break;
+                    };
+                    let dist = &ssp.tree_with(&mut ctx, graph, source, None).dist;
+                    let row = targets.iter().map(|&target| dist[target]).collect();
+                    rows.lock().expect("no worker panicked")[i] = row;
+                }
+            });
+        }
+    });
+    rows.into_inner().expect("no worker panicked")
+}
+
+impl DistanceMatrix {
+    pub fn write_csv(&self, path: &str, cost_column: &str) -> Result<()> {
+        let file = File::create(path).with_context(|| format!("creating CSV {}", path))?;
+        self.write_csv_to(file, cost_column)
+            .with_context(|| format!("writing {}", path))
+    }
+
+    /// Header `source_id` then one column per target id; the top-left cell names the cost unit,
+    /// e.g. `source_id/distance_m`.
+    pub fn write_csv_to<W: Write>(&self, w: W, cost_column: &str) -> Result<()> {
+        let mut wtr = Writer::from_writer(w);
+        let mut header = vec![format!("source_id/{}", cost_column)];
+        header.extend(self.target_ids.iter().map(|id| id.to_string()));
+        wtr.write_record(&header)?;
+        for (id, row) in self.source_ids.iter().zip(&self.dist) {
+            let mut record = vec![id.to_string()];
+            record.extend(row.iter().map(|d| format!("{:.6}", d)));
+            wtr.write_record(&record)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        let file = File::create(path).with_context(|| format!("creating {}", path))?;
+        let mut w = BufWriter::new(file);
+        self.write_to(&mut w)
+            .with_context(|| format!("writing {}", path))?;
+        w.flush().with_context(|| format!("writing {}", path))
+    }
+
+    pub fn read(path: &str) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("opening {}", path))?;
+        Self::read_from(&mut BufReader::new(file)).with_context(|| format!("reading {}", path))
+    }
+
+    pub fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        w.write_all(MAGIC)?;
+        write_u32(w, FORMAT_VERSION)?;
+        for ids in [&self.source_ids, &self.target_ids] {
+            write_u64(w, ids.len() as u64)?;
+            for &id in ids {
+                write_u64(w, id as u64)?;
+            }
+        }
+        for row in &self.dist {
+            for &d in row {
+                write_f64(w, d)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        ensure!(&magic == MAGIC, "not a distance matrix file");
+        let version = read_u32(r)?;
+        ensure!(
+            version == FORMAT_VERSION,
+            "distance matrix has format version {}, expected {}",
+            version,
+            FORMAT_VERSION
+        );
+        let read_ids = |r: &mut R| -> Result<Vec<i64>> {
+            let len = read_u64(r)? as usize;
+            (0..len).map(|_| Ok(read_u64(r)? as i64)).collect()
+        };
+        let source_ids = read_ids(r)?;
+        let target_ids = read_ids(r)?;
+        let dist = source_ids
+            .iter()
+            .map(|_| target_ids.iter().map(|_| read_f64(r)).collect())
+            .collect::<std::io::Result<_>>()?;
+        Ok(Self {
+            source_ids,
+            target_ids,
+            dist,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::{Bmssp, Dijkstra};
+    use crate::dijkstra::dijkstra_all;
+
+    #[test]
+    fn table_matches_single_source_runs() {
+        let graph = Graph::from_edges(
+            6,
+            &[
+                (0, 1, 2.0),
+                (1, 2, 1.5),
+                (0, 2, 4.0),
+                (2, 3, 1.0),
+                (3, 0, 0.5),
+                (4, 3, 2.0),
+            ],
+        );
+        let sources = [0, 4, 5, 2, 0];
+        let targets = [3, 5, 0, 1];
+        for threads in [1, 3, 16] {
+            for ssp in [
+                &Dijkstra as &(dyn ShortestPathAlgorithm + Sync),
+                &Bmssp::default(),
+            ] {
+                let table = distance_table(&graph, ssp, &sources, &targets, threads);
+                assert_eq!(table.len(), sources.len());
+                for (&source, row) in sources.iter().zip(&table) {
+                    let dist = dijkstra_all(&graph, source);
+                    let expected: Vec<f64> = targets.iter().map(|&t| dist[t]).collect();
+                    assert_eq!(row, &expected);
+                }
+            }
+        }
+        assert!(distance_table(&graph, &Dijkstra, &[], &targets, 4).is_empty());
+    }
+
+    #[test]
+    fn csv_and_binary_output() {
+        let matrix = DistanceMatrix {
+            source_ids: vec![10, -20],
+            target_ids: vec![30, 10, 40],
+            dist: vec![vec![1.5, 0.0, f64::INFINITY], vec![2.25, 3.0, 4.0]],
+        };
+        let mut csv = Vec::new();
+        matrix.write_csv_to(&mut csv, "distance_m").unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "source_id/distance_m,30,10,40\n\
+             10,1.500000,0.000000,inf\n\
+             -20,2.250000,3.000000,4.000000\n"
+        );
+
+        let mut bytes = Vec::new();
+        matrix.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 8 + 4 + 8 * (2 + 2 + 3 + 6));
+        let read = DistanceMatrix::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read, matrix);
+
+        bytes.truncate(bytes.len() - 1);
+        assert!(DistanceMatrix::read_from(&mut bytes.as_slice()).is_err());
+    }
+}
@@ -1,6 +0,0 @@
-pub mod block_data_structure;
-pub mod pq_block_list;
-pub mod chunked_search;
-pub mod geo;
-pub mod dijkstra;
-pub mod tree_block_list;
@@ -0,0 +1,386 @@
+// Builds a routable Graph from an OSM .pbf: one pass for the ways (and turn restrictions), one for
+// the node coordinates.
+
+use crate::geo;
+use crate::graph::Graph;
+use crate::profile::{Directions, Profile};
+use crate::shortest_path_tree::ShortestPathTree;
+use crate::spatial_index::SpatialIndex;
+use crate::turn_restrictions::{self, ParsedRelation, Restriction, RestrictionCounts};
+use crate::weighting::{self, Weighting};
+use anyhow::{Context, Result};
+use fnv::FnvHashMap;
+use osmpbfreader::{NodeId, OsmObj, OsmPbfReader, Tags, WayId};
+use std::collections::HashSet;
+use std::fs::File;
+
+#[derive(Clone, Debug)]
+struct WayLite {
+    id: WayId,
+    nodes: Vec<NodeId>,
+    tags: Tags,
+    directions: Directions,
+}
+
+/// Settings that change which graph gets built from an extract.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LoadOptions {
+    /// Keep only ways with a `highway` tag (recommended).
+    pub only_highways: bool,
+    pub weighting: Weighting,
+    /// Access, oneway and speed rules for a mode of travel. Without one, every highway is used in
+    /// the directions cars may take and `only_highways` applies.
+    pub profile: Option<Profile>,
+    /// Apply `type=restriction` relations (see turn_restrictions).
+    pub turn_restrictions: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            only_highways: true,
+            weighting: Weighting::Distance,
+            profile: None,
+            turn_restrictions: true,
+        }
+    }
+}
+
+/// A graph built from OSM data, plus the mapping between graph indices and OSM nodes.
+///
+/// Graph indices below `num_osm_nodes()` are OSM nodes. Turn restrictions add copies of their via
+/// nodes after those; use `collapse` or `route_to` to map results back onto the OSM nodes.
+pub struct OsmGraph {
+    pub graph: Graph,
+    pub idx_to_id: Vec<NodeId>,
+    pub id_to_idx: FnvHashMap<NodeId, usize>,
+    /// (lat, lon) per OSM node index.
+    pub coords: Vec<(f64, f64)>,
+    /// Number of routable ways the graph was built from.
+    pub num_ways: usize,
+    pub options: LoadOptions,
+    /// For graph index `num_osm_nodes() + i`, the OSM node index it is a copy of.
+    pub copy_of: Vec<usize>,
+    pub restrictions: RestrictionCounts,
+}
+
+impl OsmGraph {
+    pub fn node_index(&self, id: NodeId) -> Option<usize> {
+        self.id_to_idx.get(&id).copied()
+    }
+
+    pub fn num_osm_nodes(&self) -> usize {
+        self.idx_to_id.len()
+    }
+
+    /// The OSM node index behind a graph index.
+    pub fn original(&self, idx: usize) -> usize {
+        match idx.checked_sub(self.num_osm_nodes()) {
+            Some(copy) => self.copy_of[copy],
+            None => idx,
+        }
+    }
+
+    /// Whether the node has turn-restriction copies, so a search that stops at its own index may
+    /// not have reached it yet on every arriving way.
+    pub fn has_copies(&self, idx: usize) -> bool {
+        self.copy_of.contains(&idx)
+    }
+
+    /// Graph indices a route to OSM node index `idx` can end at: `idx` and its copies.
+    pub fn arrivals(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+        let num_osm_nodes = self.num_osm_nodes();
+        std::iter::once(idx).chain(
+            self.copy_of
+                .iter()
+                .enumerate()
+                .filter(move |&(_, &original)| original == idx)
+                .map(move |(i, _)| num_osm_nodes + i),
+        )
+    }
+
+    /// The graph index among `idx` and its copies with the smallest distance.
+    fn best_arrival(&self, dist: &[f64], idx: usize) -> usize {
+        self.arrivals(idx).fold(
+            idx,
+            |best, copy| if dist[copy] < dist[best] { copy } else { best },
+        )
+    }
+
+    /// Distances per OSM node: the minimum over each node and its copies.
+    pub fn collapse(&self, dist: &[f64]) -> Vec<f64> {
+        let num_osm_nodes = self.num_osm_nodes();
+        let mut collapsed = dist[..num_osm_nodes].to_vec();
+        for (i, &original) in self.copy_of.iter().enumerate() {
+            collapsed[original] = collapsed[original].min(dist[num_osm_nodes + i]);
+        }
+        collapsed
+    }
+
+    /// Like `collapse`, keeping each OSM node's predecessor on its shortest route (mapped to an OSM
+    /// node index). Following these predecessors back need not give a route that respects turn
+    /// restrictions; use `route_to` for routes.
+    pub fn collapse_tree(&self, tree: &ShortestPathTree) -> ShortestPathTree {
+        let num_osm_nodes = self.num_osm_nodes();
+        let mut dist = tree.dist[..num_osm_nodes].to_vec();
+        let mut pred = tree.pred[..num_osm_nodes].to_vec();
+        for (i, &original) in self.copy_of.iter().enumerate() {
+            if tree.dist[num_osm_nodes + i] < dist[original] {
+                dist[original] = tree.dist[num_osm_nodes + i];
+                pred[original] = tree.pred[num_osm_nodes + i];
+            }
+        }
+        for p in pred.iter_mut().flatten() {
+            p.node_id = self.original(p.node_id);
+        }
+        ShortestPathTree { dist, pred }
+    }
+
+    /// Route to OSM node index `target` as (OSM node index, distance so far) pairs.
+    pub fn route_to(&self, tree: &ShortestPathTree, target: usize) -> Option<Vec<(usize, f64)>> {
+        let arrival = self.best_arrival(&tree.dist, target);
+        let route = tree.path_to(arrival)?;
+        Some(
+            route
+                .into_iter()
+                .map(|idx| (self.original(idx), tree.dist[idx]))
+                .collect(),
+        )
+    }
+
+    /// Lowest cost per meter of straight-line distance over all edges: 1 with distance weights, the
+    /// inverse of the top speed with time weights.
+    pub fn cost_per_meter(&self) -> f64 {
+        let mut lowest = f64::INFINITY;
+        for u in 0..self.graph.num_nodes() {
+            let (alat, alon) = self.coords[self.original(u)];
+            for (v, weight) in self.graph.neighbors(u) {
+                let (blat, blon) = self.coords[self.original(v)];
+                let meters = geo::haversine_meters(alat, alon, blat, blon);
+                if meters > 0.0 {
+                    lowest = lowest.min(weight / meters);
+                }
+            }
+        }
+        if lowest.is_finite() {
+            lowest
+        } else {
+            0.0
+        }
+    }
+
+    /// A* heuristic towards graph index `target`: the straight-line distance scaled by
+    /// `cost_per_meter`, so it never overestimates and stays consistent. Shaved by a relative 1e-9
+    /// so rounding in the haversine formula can't break the triangle inequality.
+    pub fn heuristic_to(&self, target: usize) -> impl Fn(usize) -> f64 + '_ {
+        let scale = self.cost_per_meter() * (1.0 - 1e-9);
+        let (tlat, tlon) = self.coords[self.original(target)];
+        move |idx| {
+            let (lat, lon) = self.coords[self.original(idx)];
+            scale * geo::haversine_meters(lat, lon, tlat, tlon)
+        }
+    }
+
+    /// Spatial index over the nodes that have at least one edge, so a snapped node can actually be
+    /// routed from.
+    pub fn spatial_index(&self) -> SpatialIndex {
+        let mut routable = vec![false; self.num_osm_nodes()];
+        for u in 0..self.graph.num_nodes() {
+            for (v, _) in self.graph.neighbors(u) {
+                routable[self.original(u)] = true;
+                routable[self.original(v)] = true;
+            }
+        }
+        let points = (0..self.num_osm_nodes())
+            .filter(|&idx| routable[idx])
+            .map(|idx| (idx, self.coords[idx].0, self.coords[idx].1))
+            .collect();
+        SpatialIndex::new(points, 0.01)
+    }
+}
+
+fn is_way_routable(tags: &Tags, only_highways: bool) -> bool {
+    if only_highways && !tags.contains_key("highway") {
+        return false;
+    }
+    // Exclude areas and non-linear ways
+    if tags.get("area").map(|v| v == "yes").unwrap_or(false) {
+        return false;
+    }
+    true
+}
+
+fn is_oneway(tags: &Tags) -> Option<i8> {
+    if let Some(v) = tags.get("oneway") {
+        match v.as_str() {
+            "yes" | "true" | "1" => return Some(1),
+            "-1" => return Some(-1),
+            _ => {}
+        }
+    }
+    if tags
+        .get("junction")
+        .map(|v| v == "roundabout")
+        .unwrap_or(false)
+    {
+        return Some(1);
+    }
+    None
+}
+
+/// Reads `path` and builds the road graph, weighting each segment by `options.weighting`.
+pub fn load_pbf(path: &str, options: LoadOptions) -> Result<OsmGraph> {
+    // Pass 1: collect routable ways and the set of node ids they reference
+    let file = File::open(path).with_context(|| format!("opening {}", path))?;
+    let mut pbf = OsmPbfReader::new(file);
+
+    let mut needed_nodes: HashSet<NodeId> = HashSet::new();
+    let mut ways: Vec<WayLite> = Vec::new();
+    let mut restrictions: Vec<Restriction> = Vec::new();
+    let mut counts = RestrictionCounts::default();
+
+    for obj in pbf.iter() {
+        let obj = obj?;
+        if let OsmObj::Relation(r) = &obj {
+            if options.turn_restrictions {
+                match turn_restrictions::parse_relation(r, options.profile) {
+                    ParsedRelation::Ignored => {}
+                    ParsedRelation::Restrictions(parsed) => restrictions.extend(parsed),
+                    ParsedRelation::ViaWay => counts.via_way += 1,
+                    ParsedRelation::Invalid => counts.invalid += 1,
+                }
+            }
+        }
+        if let OsmObj::Way(w) = obj {
+            let directions = match options.profile {
+                Some(profile) => profile.directions(&w.tags),
+                None if is_way_routable(&w.tags, options.only_highways) => {
+                    Some(match is_oneway(&w.tags) {
+                        Some(1) => Directions::FORWARD,
+                        Some(-1) => Directions::BACKWARD,
+                        _ => Directions::BOTH,
+                    })
+                }
+                None => None,
+            };
+            if let Some(directions) = directions {
+                for nid in &w.nodes {
+                    needed_nodes.insert(*nid);
+                }
+                ways.push(WayLite {
+                    id: w.id,
+                    nodes: w.nodes.clone(),
+                    tags: w.tags.clone(),
+                    directions,
+                });
+            }
+        }
+    }
+
+    // Pass 2: read coordinates for needed nodes
+    let file2 = File::open(path).with_context(|| format!("reopening {}", path))?;
+    let mut pbf2 = OsmPbfReader::new(file2);
+
+    let mut coords_by_id: FnvHashMap<NodeId, (f64, f64)> = FnvHashMap::default();
+    for obj in pbf2.iter() {
+        let obj = obj?;
+        if let OsmObj::Node(n) = obj {
+            if needed_nodes.contains(&n.id) {
+                coords_by_id.insert(n.id, (n.lat(), n.lon()));
+            }
+        }
+    }
+
+    // Build index mapping and adjacency
+    let mut id_to_idx: FnvHashMap<NodeId, usize> = FnvHashMap::default();
+    let mut idx_to_id: Vec<NodeId> = Vec::with_capacity(coords_by_id.len());
+    let mut coords: Vec<(f64, f64)> = Vec::with_capacity(coords_by_id.len());
+
+    for (&nid, &coord) in coords_by_id.iter() {
+        let idx = idx_to_id.len();
+        idx_to_id.push(nid);
+        id_to_idx.insert(nid, idx);
+        coords.push(coord);
+    }
+
+    let mut edges: Vec<(usize, usize, f64, WayId)> = Vec::new();
+
+    for w in &ways {
+        if w.nodes.len() < 2 {
+            continue;
+        }
+        let speed_kmh = match options.profile {
+            Some(profile) => profile.speed_kmh(&w.tags),
+            None => weighting::speed_kmh(&w.tags),
+        };
+        for pair in w.nodes.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let (&(alat, alon), &(blat, blon)) = match (coords_by_id.get(&a), coords_by_id.get(&b))
+            {
+                (Some(ca), Some(cb)) => (ca, cb),
+                _ => continue,
+            };
+            let meters = geo::haversine_meters(alat, alon, blat, blon);
+            if meters.is_finite() && meters > 0.0 {
+                let weight = options.weighting.edge_weight(meters, speed_kmh);
+                if let (Some(&u), Some(&v)) = (id_to_idx.get(&a), id_to_idx.get(&b)) {
+                    if w.directions.forward {
+                        edges.push((u, v, weight, w.id));
+                    }
+                    if w.directions.backward {
+                        edges.push((v, u, weight, w.id));
+                    }
+                }
+            }
+        }
+    }
+
+    // Keep the restrictions whose ways are routable and both pass through the via node.
+    let way_nodes: FnvHashMap<WayId, &[NodeId]> =
+        ways.iter().map(|w| (w.id, w.nodes.as_slice())).collect();
+    let on_way = |way: WayId, node: NodeId| {
+        way_nodes
+            .get(&way)
+            .map(|nodes| nodes.contains(&node))
+            .unwrap_or(false)
+    };
+    let mut via_restrictions = Vec::with_capacity(restrictions.len());
+    for r in &restrictions {
+        match id_to_idx.get(&r.via) {
+            Some(&via) if on_way(r.from, r.via) && on_way(r.to, r.via) => {
+                via_restrictions.push((r.from, via, r.to, r.only))
+            }
+            _ => counts.invalid += 1,
+        }
+    }
+    counts.applied = via_restrictions.len();
+    let (edges, copy_of) =
+        turn_restrictions::split_via_nodes(idx_to_id.len(), &edges, &via_restrictions);
+
+    Ok(OsmGraph {
+        graph: Graph::from_edges(idx_to_id.len() + copy_of.len(), &edges),
+        idx_to_id,
+        id_to_idx,
+        coords,
+        num_ways: ways.len(),
+        options,
+        copy_of,
+        restrictions: counts,
+    })
+}
+
+/// Returns which of `ids` exist as nodes in the extract at all. Used to tell a node that isn't on a
+/// routable way apart from one that isn't in the file.
+pub fn find_nodes(path: &str, ids: &HashSet<NodeId>) -> Result<HashSet<NodeId>> {
+    let file = File::open(path).with_context(|| format!("opening {}", path))?;
+    let mut pbf = OsmPbfReader::new(file);
+    let mut found = HashSet::new();
+    for obj in pbf.iter() {
+        if let OsmObj::Node(n) = obj? {
+            if ids.contains(&n.id) {
+                found.insert(n.id);
+            }
+        }
+    }
+    Ok(found)
+}
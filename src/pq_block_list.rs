@@ -5,6 +5,10 @@ Parameterized by M, and an upper bound B over all values (assuming values are fl
 Supported operations are Add (Insert), BatchPreprend, and Pull
 */
 
+// M and B follow the paper's notation.
+#![allow(non_snake_case)]
+
+use crate::block_list::BlockListOps;
 use orx_priority_queue::*;
 
 pub type NodeId = usize;
@@ -28,6 +32,13 @@ impl BlockList {
         }
     }
 
+    /// Empties the list and sets new parameters, keeping the allocations for reuse.
+    pub fn reset(&mut self, M: usize, B: Cost) {
+        self.M = M;
+        self.B = B;
+        self.pq.clear();
+    }
+
     pub fn len(&self) -> usize {
         self.pq.len()
     }
@@ -42,8 +53,9 @@ impl BlockList {
         self.pq.decrease_key_or_push(&node_id, cost);
     }
 
-    pub fn batch_prepend(&mut self, nodes_to_prepend: Vec<(NodeId, Cost)>) {
-        for (node_id, cost) in nodes_to_prepend.into_iter() {
+    /// Inserts every element of `nodes_to_prepend`, leaving it empty (with its capacity).
+    pub fn batch_prepend(&mut self, nodes_to_prepend: &mut Vec<(NodeId, Cost)>) {
+        for (node_id, cost) in nodes_to_prepend.drain(..) {
             self.insert(node_id, cost);
         }
     }
@@ -53,17 +65,57 @@ impl BlockList {
     }
 
     pub fn pull(&mut self) -> PullResult {
-        // Pull M elements.
-        let mut pulled_elements = Vec::new();
-        for _ in 0..self.M {
+        let mut pulled_elements = Vec::with_capacity(self.M);
+        let bound = self.pull_into(&mut pulled_elements);
+        PullResult(pulled_elements, bound)
+    }
+
+    /// `pull` into a caller-owned buffer, which is cleared first. Returns the new lower bound of
+    /// the remaining elements.
+    pub fn pull_into(&mut self, pulled_elements: &mut Vec<NodeId>) -> Cost {
+        pulled_elements.clear();
+        // Pull M elements, then any left at the last one's cost, so the bound stays strictly above
+        // everything pulled.
+        let mut last_cost = None;
+        while let Some(&(_, cost)) = self.pq.peek() {
+            if pulled_elements.len() >= self.M && last_cost != Some(cost) {
+                break;
+            }
             if let Some((node_id, _)) = self.pq.pop() {
                 pulled_elements.push(node_id);
-            } else {
-                break;
             }
+            last_cost = Some(cost);
         }
 
-        PullResult(pulled_elements, self.get_minimum_bound())
+        self.get_minimum_bound()
+    }
+}
+
+impl Default for BlockList {
+    fn default() -> Self {
+        Self::new(1, Cost::INFINITY)
+    }
+}
+
+impl BlockListOps for BlockList {
+    fn reset(&mut self, M: usize, B: Cost) {
+        BlockList::reset(self, M, B)
+    }
+
+    fn insert(&mut self, node_id: NodeId, cost: Cost) {
+        BlockList::insert(self, node_id, cost)
+    }
+
+    fn batch_prepend(&mut self, nodes: &mut Vec<(NodeId, Cost)>) {
+        BlockList::batch_prepend(self, nodes)
+    }
+
+    fn pull(&mut self, pulled: &mut Vec<NodeId>) -> Cost {
+        self.pull_into(pulled)
+    }
+
+    fn is_empty(&self) -> bool {
+        BlockList::is_empty(self)
     }
 }
 
@@ -0,0 +1,297 @@
+// Vehicle profiles: which OSM ways a car, bicycle or pedestrian may use, in which direction, and at
+// what speed.
+
+use crate::weighting;
+use osmpbfreader::Tags;
+use std::fmt;
+use std::str::FromStr;
+
+/// Profile selector for the command line (`--profile car|bike|foot`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Profile {
+    Car,
+    Bike,
+    Foot,
+}
+
+/// Directions a way may be traversed in, relative to the order of its nodes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Directions {
+    pub forward: bool,
+    pub backward: bool,
+}
+
+impl Directions {
+    pub const BOTH: Directions = Directions {
+        forward: true,
+        backward: true,
+    };
+    pub const FORWARD: Directions = Directions {
+        forward: true,
+        backward: false,
+    };
+    pub const BACKWARD: Directions = Directions {
+        forward: false,
+        backward: true,
+    };
+}
+
+/// Access values that close a way to the mode they apply to.
+fn is_denied(value: &str) -> bool {
+    matches!(
+        value,
+        "no" | "private" | "agricultural" | "forestry" | "use_sidepath"
+    )
+}
+
+impl Profile {
+    /// Access keys from least to most specific; the most specific one present decides.
+    fn access_keys(self) -> &'static [&'static str] {
+        match self {
+            Profile::Car => &["access", "vehicle", "motor_vehicle", "motorcar"],
+            Profile::Bike => &["access", "vehicle", "bicycle"],
+            Profile::Foot => &["access", "foot"],
+        }
+    }
+
+    /// Keys specific enough to open a way whose highway class is closed to this mode by default
+    /// (`bicycle=yes` on a footway, but not a bare `access=yes`).
+    fn mode_keys(self) -> &'static [&'static str] {
+        match self {
+            Profile::Car => &["motor_vehicle", "motorcar"],
+            Profile::Bike => &["bicycle"],
+            Profile::Foot => &["foot"],
+        }
+    }
+
+    /// Whether the `highway` class is open to this mode when no access tag says otherwise.
+    fn allows_highway(self, highway: &str) -> bool {
+        let road = matches!(
+            highway,
+            "trunk"
+                | "trunk_link"
+                | "primary"
+                | "primary_link"
+                | "secondary"
+                | "secondary_link"
+                | "tertiary"
+                | "tertiary_link"
+                | "unclassified"
+                | "residential"
+                | "living_street"
+                | "service"
+                | "road"
+        );
+        match self {
+            Profile::Car => road || matches!(highway, "motorway" | "motorway_link"),
+            Profile::Bike => road || matches!(highway, "cycleway" | "path" | "track"),
+            Profile::Foot => {
+                road || matches!(
+                    highway,
+                    "footway" | "path" | "pedestrian" | "steps" | "track"
+                )
+            }
+        }
+    }
+
+    fn has_access(self, tags: &Tags, highway: &str) -> bool {
+        let by_class = self.allows_highway(highway);
+        let decisive = self
+            .access_keys()
+            .iter()
+            .rev()
+            .find_map(|&key| tags.get(key).map(|value| (key, value.as_str())));
+        match decisive {
+            Some((_, value)) if is_denied(value) => false,
+            Some((key, _)) => by_class || self.mode_keys().contains(&key),
+            None => by_class,
+        }
+    }
+
+    /// Directions this mode may use the way in, or None if it can't use it at all.
+    pub fn directions(self, tags: &Tags) -> Option<Directions> {
+        let highway = tags.get("highway")?.as_str();
+        if tags.get("area").map(|v| v == "yes").unwrap_or(false) || !self.has_access(tags, highway)
+        {
+            return None;
+        }
+        match self {
+            Profile::Car => vehicle_oneway(tags, highway),
+            Profile::Bike => {
+                let bike_oneway = tags.get("oneway:bicycle").map(|v| v.as_str());
+                let contraflow = bike_oneway == Some("no")
+                    || tags
+                        .get("cycleway")
+                        .map(|v| v.starts_with("opposite"))
+                        .unwrap_or(false);
+                if contraflow {
+                    Some(Directions::BOTH)
+                } else if let Some(directions) = bike_oneway.and_then(oneway_value) {
+                    Some(directions)
+                } else {
+                    vehicle_oneway(tags, highway)
+                }
+            }
+            Profile::Foot => Some(
+                tags.get("oneway:foot")
+                    .and_then(|v| oneway_value(v))
+                    .unwrap_or(Directions::BOTH),
+            ),
+        }
+    }
+
+    /// Travel speed along the way in km/h, for `--weight time`.
+    pub fn speed_kmh(self, tags: &Tags) -> f64 {
+        let highway = tags.get("highway").map(|v| v.as_str()).unwrap_or("");
+        match self {
+            Profile::Car => weighting::speed_kmh(tags),
+            Profile::Bike => {
+                let cruising: f64 = if matches!(highway, "track" | "path") {
+                    12.0
+                } else {
+                    15.0
+                };
+                cruising.min(weighting::speed_kmh(tags))
+            }
+            Profile::Foot => {
+                if highway == "steps" {
+                    3.0
+                } else {
+                    5.0
+                }
+            }
+        }
+    }
+}
+
+/// A oneway-style tag value: "yes"/"true"/"1" forward, "-1"/"reverse" backward.
+fn oneway_value(value: &str) -> Option<Directions> {
+    match value {
+        "yes" | "true" | "1" => Some(Directions::FORWARD),
+        "-1" | "reverse" => Some(Directions::BACKWARD),
+        _ => None,
+    }
+}
+
+/// Oneway rules for vehicles: the `oneway` tag, else implied by roundabouts and motorways. Ways
+/// whose direction changes over time (`oneway=reversible`) are left out.
+fn vehicle_oneway(tags: &Tags, highway: &str) -> Option<Directions> {
+    match tags.get("oneway").map(|v| v.as_str()) {
+        Some("reversible" | "alternating") => None,
+        Some("no" | "false" | "0") => Some(Directions::BOTH),
+        Some(v) if oneway_value(v).is_some() => oneway_value(v),
+        _ => {
+            let implied = matches!(highway, "motorway" | "motorway_link")
+                || tags
+                    .get("junction")
+                    .map(|v| v == "roundabout")
+                    .unwrap_or(false);
+            Some(if implied {
+                Directions::FORWARD
+            } else {
+                Directions::BOTH
+            })
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "car" => Ok(Profile::Car),
+            "bike" => Ok(Profile::Bike),
+            "foot" => Ok(Profile::Foot),
+            _ => Err(format!(
+                "Profile not found for input string: {}, possible options are: (\"car\", \"bike\", \"foot\")",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Profile::Car => "car",
+            Profile::Bike => "bike",
+            Profile::Foot => "foot",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(pairs: &[(&str, &str)]) -> Tags {
+        pairs.iter().map(|&(k, v)| (k.into(), v.into())).collect()
+    }
+
+    #[test]
+    fn access_by_mode() {
+        let footway = tags(&[("highway", "footway")]);
+        assert_eq!(Profile::Car.directions(&footway), None);
+        assert_eq!(Profile::Bike.directions(&footway), None);
+        assert_eq!(Profile::Foot.directions(&footway), Some(Directions::BOTH));
+
+        let shared = tags(&[("highway", "footway"), ("bicycle", "yes")]);
+        assert_eq!(Profile::Bike.directions(&shared), Some(Directions::BOTH));
+        // A generic access=yes doesn't open a footway to cars.
+        let open = tags(&[("highway", "footway"), ("access", "yes")]);
+        assert_eq!(Profile::Car.directions(&open), None);
+
+        let motorway = tags(&[("highway", "motorway")]);
+        assert_eq!(
+            Profile::Car.directions(&motorway),
+            Some(Directions::FORWARD)
+        );
+        assert_eq!(Profile::Bike.directions(&motorway), None);
+        assert_eq!(Profile::Foot.directions(&motorway), None);
+
+        // The most specific access tag wins.
+        let bus_only = tags(&[("highway", "residential"), ("motor_vehicle", "no")]);
+        assert_eq!(Profile::Car.directions(&bus_only), None);
+        assert_eq!(Profile::Bike.directions(&bus_only), Some(Directions::BOTH));
+        let residents = tags(&[
+            ("highway", "residential"),
+            ("access", "no"),
+            ("motorcar", "destination"),
+        ]);
+        assert_eq!(Profile::Car.directions(&residents), Some(Directions::BOTH));
+        assert_eq!(Profile::Foot.directions(&residents), None);
+    }
+
+    #[test]
+    fn oneway_by_mode() {
+        let oneway = tags(&[("highway", "residential"), ("oneway", "yes")]);
+        assert_eq!(Profile::Car.directions(&oneway), Some(Directions::FORWARD));
+        assert_eq!(Profile::Bike.directions(&oneway), Some(Directions::FORWARD));
+        assert_eq!(Profile::Foot.directions(&oneway), Some(Directions::BOTH));
+
+        let contraflow = tags(&[
+            ("highway", "residential"),
+            ("oneway", "-1"),
+            ("oneway:bicycle", "no"),
+        ]);
+        assert_eq!(
+            Profile::Car.directions(&contraflow),
+            Some(Directions::BACKWARD)
+        );
+        assert_eq!(
+            Profile::Bike.directions(&contraflow),
+            Some(Directions::BOTH)
+        );
+
+        let lane = tags(&[
+            ("highway", "tertiary"),
+            ("oneway", "yes"),
+            ("cycleway", "opposite_lane"),
+        ]);
+        assert_eq!(Profile::Bike.directions(&lane), Some(Directions::BOTH));
+
+        let reversible = tags(&[("highway", "primary"), ("oneway", "reversible")]);
+        assert_eq!(Profile::Car.directions(&reversible), None);
+    }
+}
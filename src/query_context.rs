@@ -0,0 +1,189 @@
+// Buffers a search needs, kept between queries so that repeated queries on the same graph stop
+// allocating once the buffers have grown to fit.
+//
+// The labels are reset sparsely: every node whose label a query sets is remembered, and only those
+// are cleared before the next query, so a short search doesn't pay for touching the whole graph.
+
+use crate::bmssp::Level;
+use crate::shortest_path_tree::{Predecessor, ShortestPathTree};
+use crate::{block_data_structure, pq_block_list, tree_block_list};
+use hashbrown::{HashMap, HashSet};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+#[derive(Copy, Clone, PartialEq)]
+pub(crate) struct State {
+    pub cost: f64,
+    pub node_id: usize,
+}
+
+// Min-heap by cost
+impl Eq for State {}
+
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reverse ordering for min-heap
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Reusable workspace for Dijkstra and BMSSP queries (see `dijkstra_with`, `bmssp_with` and
+/// `ShortestPathAlgorithm::tree_with`). One context serves one query at a time; use one per thread.
+#[derive(Default)]
+pub struct QueryContext {
+    pub(crate) tree: ShortestPathTree,
+    /// Nodes whose label differs from unreached.
+    touched: Vec<usize>,
+    pub(crate) heap: BinaryHeap<State>,
+    // BMSSP scratch: per recursion level, then for find_pivots and the base case.
+    pub(crate) levels: Vec<Level>,
+    pub(crate) paper_block_lists: Vec<block_data_structure::BlockList>,
+    pub(crate) tree_block_lists: Vec<tree_block_list::BlockList>,
+    pub(crate) pq_block_lists: Vec<pq_block_list::BlockList>,
+    pub(crate) u_set: Vec<usize>,
+    pub(crate) parents: HashMap<usize, usize>,
+    pub(crate) tree_sizes: HashMap<usize, usize>,
+    pub(crate) node_to_root: HashMap<usize, usize>,
+    pub(crate) branch: Vec<usize>,
+    pub(crate) visited: HashSet<usize>,
+    /// Nodes BMSSP has already returned in some U.
+    pub(crate) settled: Vec<bool>,
+}
+
+impl QueryContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Result of the last query.
+    pub fn tree(&self) -> &ShortestPathTree {
+        &self.tree
+    }
+
+    pub fn into_tree(self) -> ShortestPathTree {
+        self.tree
+    }
+
+    /// Nodes the last query labelled; every other node is unreached.
+    pub(crate) fn touched(&self) -> &[usize] {
+        &self.touched
+    }
+
+    /// Clears the last query's labels and labels each `(source, offset)` (the smallest offset if a
+    /// source is listed twice), as `ShortestPathTree::with_sources` would.
+    pub(crate) fn start(&mut self, num_nodes: usize, sources: &[(usize, f64)]) {
+        if self.tree.dist.len() == num_nodes {
+            // Settled nodes all have a label, so they were touched too.
+            for node_id in self.touched.drain(..) {
+                self.tree.dist[node_id] = f64::INFINITY;
+                self.tree.pred[node_id] = None;
+                self.settled[node_id] = false;
+            }
+        } else {
+            self.tree = ShortestPathTree::with_sources(num_nodes, &[]);
+            self.touched.clear();
+            self.settled = vec![false; num_nodes];
+        }
+        for &(source, offset) in sources {
+            if offset < self.tree.dist[source] {
+                if self.tree.dist[source].is_infinite() {
+                    self.touched.push(source);
+                }
+                self.tree.dist[source] = offset;
+            }
+        }
+        self.heap.clear();
+    }
+
+    /// `ShortestPathTree::set`, remembering the node for the next reset.
+    #[inline(always)]
+    pub(crate) fn set(&mut self, node_id: usize, cost: f64, pred: Predecessor) {
+        if self.tree.dist[node_id].is_infinite() {
+            self.touched.push(node_id);
+        }
+        self.tree.set(node_id, cost, pred);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::{Bmssp, Dijkstra, ShortestPathAlgorithm};
+    use crate::block_list::BlockListKind;
+    use crate::bmssp::bmssp_with;
+    use crate::dijkstra::{dijkstra_all, dijkstra_with};
+    use crate::graph::Graph;
+    use crate::test_util::random_graph;
+
+    #[test]
+    fn reused_context_matches_fresh_searches() {
+        let n = 300;
+        let graph = random_graph(n, 3, 5);
+        let small = Graph::from_edges(3, &[(0, 1, 2.0), (1, 2, 1.5)]);
+
+        let mut ctx = QueryContext::new();
+        // Point-to-point queries leave stale labels behind for the next query to clear; a query on
+        // another graph resizes the buffers.
+        for (start, target) in [
+            (0, Some(17)),
+            (5, None),
+            (9, Some(9)),
+            (0, None),
+            (42, Some(3)),
+        ] {
+            let expected = dijkstra_all(&graph, start);
+            let tree = dijkstra_with(&mut ctx, &graph, start, target);
+            match target {
+                Some(target) => assert_eq!(tree.dist[target], expected[target]),
+                None => assert_eq!(tree.dist, expected),
+            }
+            assert_eq!(tree.pred[start], None);
+            // Each block list backend keeps its own per-level lists in the context.
+            for block_list in BlockListKind::ALL {
+                let bmssp = Bmssp::new(block_list);
+                let tree = bmssp.tree_with(&mut ctx, &graph, start, target);
+                match target {
+                    Some(target) => assert_eq!(tree.dist[target], expected[target]),
+                    None => assert_eq!(tree.dist, expected),
+                }
+                assert_eq!(tree.path_to(start), Some(vec![start]));
+            }
+            assert_eq!(
+                bmssp_with(&mut ctx, &small, 1, None).dist,
+                vec![f64::INFINITY, 0.0, 1.5]
+            );
+        }
+
+        // Bounded searches label the nodes `within` returns below the bound.
+        let mut solvers: Vec<Box<dyn ShortestPathAlgorithm>> = vec![Box::new(Dijkstra)];
+        for block_list in BlockListKind::ALL {
+            solvers.push(Box::new(Bmssp::new(block_list)));
+        }
+        for solver in &solvers {
+            for (start, max_cost) in [(0, 3.0), (5, 0.0), (42, 10.0), (0, f64::INFINITY)] {
+                let expected = solver.within(&graph, start, max_cost);
+                let tree = solver.within_with(&mut ctx, &graph, start, max_cost);
+                let mut below: Vec<(usize, f64)> = tree
+                    .dist
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &dist)| dist < max_cost)
+                    .map(|(node_id, &dist)| (node_id, dist))
+                    .collect();
+                below.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+                let mut expected_sorted = expected.clone();
+                expected_sorted.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+                assert_eq!(below, expected_sorted, "{} from {}", solver.name(), start);
+            }
+        }
+    }
+}
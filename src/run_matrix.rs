@@ -0,0 +1,219 @@
+use anyhow::{bail, Context, Result};
+use clap::{ArgGroup, Parser};
+use csv::Reader;
+use osmpbfreader::NodeId;
+use ssps::ch::ContractionHierarchy;
+use ssps::graph_cache::GraphCache;
+use ssps::matrix::{self, DistanceMatrix, MatrixFormat};
+use ssps::osm_loader::{self, LoadOptions, OsmGraph};
+use ssps::profile::Profile;
+use ssps::spatial_index::SpatialIndex;
+use ssps::weighting::Weighting;
+use ssps::Algorithm;
+use std::time::Instant;
+
+#[derive(Parser, Debug)]
+#[command(name = "matrix")]
+#[command(about = "Compute a source x target distance matrix (e.g. depots to stops) over an OSM graph.", long_about = None)]
+#[command(group(ArgGroup::new("input").required(true).multiple(true).args(["pbf", "graph"])))]
+struct Cli {
+    /// Path to the .osm.pbf file. With --graph, only used to check the cache is up to date.
+    #[arg(short, long)]
+    pbf: Option<String>,
+
+    /// Graph cache written by build-graph; skips parsing the .pbf.
+    #[arg(short, long)]
+    graph: Option<String>,
+
+    /// CSV of sources, one per row: a `node_id` column with OSM node ids, or `lat` and `lon`
+    /// columns that snap to the nearest routable node.
+    #[arg(short, long)]
+    sources: String,
+
+    /// CSV of targets in the same form as --sources. Defaults to the sources.
+    #[arg(short, long)]
+    targets: Option<String>,
+
+    /// Output file, one row per source and one column per target.
+    #[arg(short, long)]
+    out: String,
+
+    /// Output format: csv, or binary (ids and row-major f64s, see ssps::matrix).
+    #[arg(short, long, default_value_t = MatrixFormat::Csv)]
+    format: MatrixFormat,
+
+    #[arg(short, long, default_value_t = Algorithm::Bmssp)]
+    algorithm: Algorithm,
+
+    /// Worker threads for the per-source searches. Defaults to the available parallelism.
+    #[arg(long)]
+    threads: Option<usize>,
+
+    /// Contraction hierarchy written by `build-graph --ch` for the same graph. Computes the matrix
+    /// with one small upward search per source and per target instead of a full search per source.
+    #[arg(long)]
+    ch: Option<String>,
+
+    /// Only include 'highway' ways (recommended). If false, attempts to include all linear ways.
+    #[arg(long, default_value_t = true)]
+    only_highways: bool,
+
+    /// Don't apply turn restrictions (type=restriction relations) when building the graph.
+    #[arg(long, default_value_t = false)]
+    ignore_turn_restrictions: bool,
+
+    /// Edge weights: segment length in meters (default), or travel time in seconds. With --graph,
+    /// must match what the cache was built with.
+    #[arg(short, long)]
+    weight: Option<Weighting>,
+
+    /// Mode of travel (car, bike, foot). With --graph, must match what the cache was built with.
+    #[arg(long)]
+    profile: Option<Profile>,
+}
+
+/// Reads a node list (see --sources) and maps every row to a graph index.
+fn read_nodes(
+    path: &str,
+    osm: &OsmGraph,
+    snap_index: &mut Option<SpatialIndex>,
+) -> Result<Vec<usize>> {
+    let mut rdr = Reader::from_path(path).with_context(|| format!("opening CSV {}", path))?;
+    let headers = rdr.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|h| h.trim() == name);
+    let (id_column, lat_lon) = match (column("node_id"), column("lat"), column("lon")) {
+        (Some(id), _, _) => (Some(id), None),
+        (None, Some(lat), Some(lon)) => (None, Some((lat, lon))),
+        _ => bail!("{} needs a node_id column, or lat and lon columns", path),
+    };
+    let mut nodes = Vec::new();
+    for (row, record) in rdr.records().enumerate() {
+        let record = record.with_context(|| format!("reading {}", path))?;
+        let field = |i: usize| record.get(i).unwrap_or("").trim();
+        // Row numbers count the header as row 1, as spreadsheets do.
+        let row = row + 2;
+        let idx = match (id_column, lat_lon) {
+            (Some(id_column), _) => {
+                let id: i64 = field(id_column)
+                    .parse()
+                    .with_context(|| format!("{} row {}: invalid node_id", path, row))?;
+                match osm.node_index(NodeId(id)) {
+                    Some(idx) => idx,
+                    None => bail!("{} row {}: node {} is not in the graph", path, row, id),
+                }
+            }
+            (None, Some((lat_column, lon_column))) => {
+                let lat: f64 = field(lat_column)
+                    .parse()
+                    .with_context(|| format!("{} row {}: invalid lat", path, row))?;
+                let lon: f64 = field(lon_column)
+                    .parse()
+                    .with_context(|| format!("{} row {}: invalid lon", path, row))?;
+                let index = snap_index.get_or_insert_with(|| osm.spatial_index());
+                index
+                    .nearest(lat, lon)
+                    .context("graph has no routable nodes to snap to")?
+                    .0
+            }
+            (None, None) => unreachable!("checked against the header"),
+        };
+        nodes.push(idx);
+    }
+    Ok(nodes)
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    let osm = match (&cli.graph, &cli.pbf) {
+        (Some(graph_path), pbf) => {
+            let cache = GraphCache::read(graph_path)?;
+            if let Some(pbf) = pbf {
+                cache.check_source(pbf)?;
+            }
+            cache.check_options(
+                graph_path,
+                cli.weight,
+                cli.profile,
+                cli.ignore_turn_restrictions,
+            )?;
+            println!("Loaded graph cache {}", graph_path);
+            cache.into_osm_graph()?
+        }
+        (None, Some(pbf)) => {
+            let options = LoadOptions {
+                only_highways: cli.only_highways,
+                weighting: cli.weight.unwrap_or_default(),
+                profile: cli.profile,
+                turn_restrictions: !cli.ignore_turn_restrictions,
+            };
+            osm_loader::load_pbf(pbf, options)?
+        }
+        (None, None) => unreachable!("clap requires --pbf or --graph"),
+    };
+    let graph = &osm.graph;
+    println!(
+        "Graph: {} nodes, {} directed edges",
+        graph.num_nodes(),
+        graph.num_edges()
+    );
+
+    let mut snap_index = None;
+    let sources = read_nodes(&cli.sources, &osm, &mut snap_index)?;
+    let targets = match &cli.targets {
+        Some(path) => read_nodes(path, &osm, &mut snap_index)?,
+        None => sources.clone(),
+    };
+    println!("{} sources x {} targets", sources.len(), targets.len());
+
+    // A via node is reached through one of its copies: search to every copy, keep the cheapest.
+    let mut arrivals = Vec::new();
+    let mut column_of = Vec::new();
+    for (column, &target) in targets.iter().enumerate() {
+        for arrival in osm.arrivals(target) {
+            arrivals.push(arrival);
+            column_of.push(column);
+        }
+    }
+
+    let now = Instant::now();
+    let table = match &cli.ch {
+        Some(ch_path) => {
+            let ch = ContractionHierarchy::read(ch_path)?;
+            ch.check_graph(graph)
+                .with_context(|| format!("loading {}", ch_path))?;
+            ch.many_to_many(&sources, &arrivals)
+        }
+        None => {
+            let threads = cli
+                .threads
+                .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+            let ssp = cli.algorithm.solver();
+            matrix::distance_table(graph, ssp.as_ref(), &sources, &arrivals, threads)
+        }
+    };
+    println!("{:.3} s", now.elapsed().as_secs_f64());
+
+    let dist = table
+        .into_iter()
+        .map(|row| {
+            let mut collapsed = vec![f64::INFINITY; targets.len()];
+            for (&column, d) in column_of.iter().zip(row) {
+                collapsed[column] = collapsed[column].min(d);
+            }
+            collapsed
+        })
+        .collect();
+    let ids = |nodes: &[usize]| nodes.iter().map(|&idx| osm.idx_to_id[idx].0).collect();
+    let matrix = DistanceMatrix {
+        source_ids: ids(&sources),
+        target_ids: ids(&targets),
+        dist,
+    };
+    match cli.format {
+        MatrixFormat::Csv => matrix.write_csv(&cli.out, osm.options.weighting.column())?,
+        MatrixFormat::Binary => matrix.write(&cli.out)?,
+    }
+    println!("Wrote {}", cli.out);
+    Ok(())
+}
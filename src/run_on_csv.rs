@@ -1,109 +1,276 @@
-use anyhow::{Context, Result};
-use clap::Parser;
-use csv::{ReaderBuilder, Writer};
-use std::collections::{HashMap, HashSet};
+use clap::{ArgGroup, Parser};
+use ssps::block_list::BlockListKind;
+use ssps::graph_cache::GraphCache;
+use ssps::verify::Comparison;
+use ssps::{csv_loader, dijkstra, tune, Algorithm, Bmssp, BmsspParams, QueryContext};
 use std::error::Error;
-
-mod block_data_structure;
-mod bmssp;
-mod dijkstra;
-mod pq_block_list;
-mod tree_block_list;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
 
 #[derive(Parser, Debug)]
 #[command(name = "ssps")]
 #[command(about = "Build a graph from a CSV with node_id,neighbors, and weights per row run an SSP algorithm from a source node id.", long_about = None)]
+#[command(group(ArgGroup::new("input").required(true).args(["csv", "graph"])))]
 struct Cli {
     /// Path to the .csv file
     #[arg(short, long)]
-    csv: String,
+    csv: Option<String>,
+
+    /// Graph cache written by build-graph, loaded instead of parsing a CSV.
+    #[arg(short, long)]
+    graph: Option<String>,
 
-    /// Number of runs. Picks a new (deterministic) source id per run (0, 1, 2, 3, ...num_runs).
+    /// Number of runs. Picks a new (deterministic) source id per run (0, 1, 2, 3, ...num_runs), so
+    /// at most the number of nodes.
     #[arg(short, long)]
     num_runs: usize,
 
-    #[arg(short, long, default_value_t = String::from("bmssp"))]
-    algorithm: String,
-}
+    #[arg(short, long, default_value_t = Algorithm::Bmssp)]
+    algorithm: Algorithm,
 
-enum SspAlgorithm {
-    Bmssp,
-    Dijkstra,
-}
+    /// Block list backend BMSSP runs on (paper, tree or pq), to benchmark them against each other.
+    #[arg(long, default_value_t = BlockListKind::Tree)]
+    block_list: BlockListKind,
 
-impl SspAlgorithm {
-    fn from(string: &str) -> Self {
-        match string {
-            "bmssp" => SspAlgorithm::Bmssp{},
-            "dijkstra" => SspAlgorithm::Dijkstra{},
-            _ => panic!("Algorithm not found for input string: {}, possible options are: (\"bmssp\", \"dijkstra\")", string),
-        }
-    }
+    /// BMSSP's k (see BmsspParams), instead of floor(log^(1/3) N).
+    #[arg(long)]
+    k: Option<usize>,
 
-    fn run(self: &Self, neighbors: &Vec<Vec<(usize, f64)>>, start: usize) -> Vec<f64> {
-        match self {
-            SspAlgorithm::Bmssp => bmssp::bmssp_all(neighbors, start),
-            SspAlgorithm::Dijkstra => dijkstra::dijkstra_all(neighbors, start),
-        }
-    }
-}
+    /// BMSSP's t, instead of floor(log^(2/3) N).
+    #[arg(long)]
+    t: Option<usize>,
 
-fn build_adjacency_list(edges: &[(usize, usize, f64)]) -> Vec<Vec<(usize, f64)>> {
-    // First, determine how many nodes we have.
-    let max_node = edges
-        .iter()
-        .map(|(u, v, _)| std::cmp::max(*u, *v))
-        .max()
-        .unwrap_or(0);
+    /// BMSSP's top level l, instead of ceil(log N / t).
+    #[arg(long)]
+    l: Option<usize>,
 
-    let mut adj: Vec<Vec<(usize, f64)>> = vec![Vec::new(); max_node + 1];
+    /// Instead of timing one setting, sweep BMSSP's k, t and l over candidates around the paper's
+    /// values, timing --num-runs sources spread over the graph with each, and report the fastest.
+    #[arg(long, conflicts_with_all = ["max_distance", "threads", "reuse_context", "verify", "k", "t", "l"])]
+    tune: bool,
 
-    for &(u, v, w) in edges {
-        adj[u].push((v, w));
-    }
+    /// Only search nodes closer than this to the source.
+    #[arg(long)]
+    max_distance: Option<f64>,
+
+    /// Run the sources concurrently on this many worker threads sharing the graph, and report
+    /// throughput alongside latency. Each worker runs its queries through its own QueryContext, as
+    /// with --reuse-context. Without it, runs are sequential.
+    #[arg(long)]
+    threads: Option<usize>,
 
-    adj
+    /// Run the sequential queries through one QueryContext, reusing its buffers instead of
+    /// allocating new ones per query.
+    #[arg(long)]
+    reuse_context: bool,
+
+    /// Instead of timing, compare every run's distances with Dijkstra's. Reports the sources that
+    /// disagree and fails if there are any.
+    #[arg(long, conflicts_with_all = ["max_distance", "threads", "reuse_context"])]
+    verify: bool,
 }
 
-fn parse_csv_and_build_adjacency_list(
-    path: &str,
-) -> Result<Vec<Vec<(usize, f64)>>, Box<dyn Error>> {
-    let mut rdr = ReaderBuilder::new()
-        .has_headers(true) // important: skip header line
-        .from_path(path)?;
-
-    let mut edges = Vec::new();
-
-    for result in rdr.records() {
-        let record = result?;
-        let node_id: usize = record[0].parse()?;
-        let neighbor_node_id: usize = record[1].parse()?;
-        let weight: f64 = record[2].parse()?;
-        edges.push((node_id, neighbor_node_id, weight));
-    }
+/// Value at fraction `q` of `sorted` (nearest rank).
+fn percentile(sorted: &[f64], q: f64) -> f64 {
+    let rank = (q * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
 
-    Ok(build_adjacency_list(&edges))
+fn print_summary(mut duration_millis: Vec<f64>, wall_secs: f64) {
+    if duration_millis.is_empty() {
+        return;
+    }
+    let latencies: Vec<String> = duration_millis
+        .iter()
+        .map(|millis| format!("{:.3}", millis))
+        .collect();
+    println!("Latencies (ms): {}", latencies.join(", "));
+    duration_millis.sort_by(f64::total_cmp);
+    let mean = duration_millis.iter().sum::<f64>() / duration_millis.len() as f64;
+    println!(
+        "{} queries in {:.3} s: {:.1} queries/s",
+        duration_millis.len(),
+        wall_secs,
+        duration_millis.len() as f64 / wall_secs
+    );
+    println!(
+        "Latency (ms): mean {:.3}, p50 {:.3}, p90 {:.3}, p99 {:.3}, max {:.3}",
+        mean,
+        percentile(&duration_millis, 0.5),
+        percentile(&duration_millis, 0.9),
+        percentile(&duration_millis, 0.99),
+        duration_millis[duration_millis.len() - 1]
+    );
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let cli = Cli::parse();
 
+    let graph = match (&cli.csv, &cli.graph) {
+        (_, Some(graph_path)) => GraphCache::read(graph_path)?.graph,
+        (Some(csv), None) => csv_loader::load_csv(csv)?,
+        (None, None) => unreachable!("clap requires --csv or --graph"),
+    };
+    if cli.num_runs > graph.num_nodes() {
+        return Err(format!(
+            "--num-runs {} is more than the {} nodes in the graph",
+            cli.num_runs,
+            graph.num_nodes()
+        )
+        .into());
+    }
+
     // Set the algorithm.
-    let ssp = SspAlgorithm::from(&cli.algorithm);
-
-    let adj = parse_csv_and_build_adjacency_list(&cli.csv)?;
-
-    let mut src_idx = 0;
-    let mut duration_millis = Vec::new();
-    for src_idx in 0..cli.num_runs {
-        use std::time::SystemTime;
-        let now = SystemTime::now();
-        let dist = ssp.run(&adj, src_idx);
-        if let Ok(elapsed) = now.elapsed() {
-            duration_millis.push(elapsed.as_secs_f64() * 1000.0);
+    let params = match (cli.k, cli.t, cli.l) {
+        (None, None, None) => None,
+        (k, t, l) => Some(BmsspParams::with_overrides(graph.num_nodes(), k, t, l)?),
+    };
+    let mut bmssp = Bmssp::new(cli.block_list);
+    if let Some(params) = params {
+        bmssp = bmssp.with_params(params, graph.num_nodes())?;
+    }
+    let ssp = cli.algorithm.solver_with(bmssp);
+
+    if cli.tune {
+        if cli.num_runs == 0 {
+            return Err("--tune needs at least one run".into());
+        }
+        let num_nodes = graph.num_nodes();
+        let sources: Vec<usize> = (0..cli.num_runs)
+            .map(|i| i * num_nodes / cli.num_runs)
+            .collect();
+        let paper = BmsspParams::for_graph(num_nodes);
+        let candidates = tune::candidates(num_nodes);
+        println!(
+            "Timing {} candidates on {} sources ({} block list)",
+            candidates.len(),
+            sources.len(),
+            cli.block_list
+        );
+        let tune::Sweep { trials, wrong } = tune::sweep(&graph, bmssp, &sources, &candidates);
+        for params in &wrong {
+            println!("{}: distances differ from Dijkstra's, dropped", params);
+        }
+        for trial in trials.iter().take(10) {
+            println!("{}: {:.3} ms", trial.params, trial.mean_millis);
+        }
+        if let (Some(fastest), Some(default)) = (
+            trials.first(),
+            trials.iter().find(|trial| trial.params == paper),
+        ) {
+            println!(
+                "Fastest: {} at {:.3} ms; the paper's {} takes {:.3} ms ({:.2}x)",
+                fastest.params,
+                fastest.mean_millis,
+                paper,
+                default.mean_millis,
+                default.mean_millis / fastest.mean_millis
+            );
+        }
+        return Ok(());
+    }
+
+    if cli.verify {
+        let mut failed = 0;
+        let (mut max_abs_error, mut max_rel_error) = (0.0_f64, 0.0_f64);
+        for src_idx in 0..cli.num_runs {
+            let expected = dijkstra::dijkstra_all(&graph, src_idx);
+            let comparison = Comparison::new(&expected, &ssp.distances(&graph, src_idx));
+            max_abs_error = max_abs_error.max(comparison.max_abs_error);
+            max_rel_error = max_rel_error.max(comparison.max_rel_error);
+            if !comparison.is_match() {
+                failed += 1;
+                println!("Source {}: {}", src_idx, comparison);
+                for &(node_id, e, a) in &comparison.examples {
+                    println!(
+                        "  node {}: dijkstra {:.6}, {} {:.6}",
+                        node_id,
+                        e,
+                        ssp.name(),
+                        a
+                    );
+                }
+            }
         }
+        println!(
+            "{} vs dijkstra: {} of {} sources differ (max abs error {:.6e}, max rel error {:.3e})",
+            ssp.name(),
+            failed,
+            cli.num_runs,
+            max_abs_error,
+            max_rel_error
+        );
+        if failed > 0 {
+            return Err(format!(
+                "{} disagrees with dijkstra from {} sources",
+                ssp.name(),
+                failed
+            )
+            .into());
+        }
+        return Ok(());
     }
-    println!("{:?}", duration_millis);
+
+    // The thread pool always reuses its workers' contexts, so its throughput leaves out allocation.
+    let reuse_context = cli.reuse_context || cli.threads.is_some();
+    let run = |ctx: &mut QueryContext, src_idx: usize| match cli.max_distance {
+        Some(max_cost) if reuse_context => {
+            ssp.within_with(ctx, &graph, src_idx, max_cost);
+        }
+        Some(max_cost) => {
+            ssp.within(&graph, src_idx, max_cost);
+        }
+        None if reuse_context => {
+            ssp.tree_with(ctx, &graph, src_idx, None);
+        }
+        None => {
+            ssp.distances(&graph, src_idx);
+        }
+    };
+
+    let wall = Instant::now();
+    let duration_millis = match cli.threads {
+        None => {
+            let mut ctx = QueryContext::new();
+            let mut duration_millis = Vec::new();
+            for src_idx in 0..cli.num_runs {
+                let now = Instant::now();
+                run(&mut ctx, src_idx);
+                duration_millis.push(now.elapsed().as_secs_f64() * 1000.0);
+            }
+            duration_millis
+        }
+        Some(threads) => {
+            // Workers take the next source off a shared counter; the graph is only read, and each
+            // worker keeps its own context and timings.
+            let next = AtomicUsize::new(0);
+            thread::scope(|scope| {
+                let workers: Vec<_> = (0..threads.max(1))
+                    .map(|_| {
+                        scope.spawn(|| {
+                            let mut ctx = QueryContext::new();
+                            let mut duration_millis = Vec::new();
+                            loop {
+                                let src_idx = next.fetch_add(1, Ordering::Relaxed);
+                                if src_idx >= cli.num_runs {
+                                    break duration_millis;
+                                }
+                                let now = Instant::now();
+                                run(&mut ctx, src_idx);
+                                duration_millis.push(now.elapsed().as_secs_f64() * 1000.0);
+                            }
+                        })
+                    })
+                    .collect();
+                workers
+                    .into_iter()
+                    .flat_map(|worker| worker.join().expect("worker panicked"))
+                    .collect()
+            })
+        }
+    };
+    print_summary(duration_millis, wall.elapsed().as_secs_f64());
 
     Ok(())
 }
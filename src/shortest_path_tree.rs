@@ -0,0 +1,157 @@
+// Shortest-path tree shared by the SSP algorithms: a distance label and a back pointer per node.
+
+/// The node we arrived from, plus the id of the edge we took out of it
+/// (i.e. `graph.edge(edge_idx)` is the incoming edge).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Predecessor {
+    pub node_id: usize,
+    pub edge_idx: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ShortestPathTree {
+    pub dist: Vec<f64>,
+    pub pred: Vec<Option<Predecessor>>,
+}
+
+/// Result of a multi-source search: a shortest-path forest rooted at the sources, plus the source
+/// each node's shortest path starts from (a network Voronoi partition).
+#[derive(Clone, Debug)]
+pub struct MultiSourceTree {
+    pub tree: ShortestPathTree,
+    /// Owning source per node, None when unreachable.
+    pub owner: Vec<Option<usize>>,
+}
+
+impl MultiSourceTree {
+    pub fn new(tree: ShortestPathTree) -> Self {
+        let owner = tree.roots();
+        Self { tree, owner }
+    }
+}
+
+impl ShortestPathTree {
+    /// All nodes start unreachable, except `start` at cost 0 with no predecessor.
+    pub fn new(num_nodes: usize, start: usize) -> Self {
+        Self::with_sources(num_nodes, &[(start, 0.0)])
+    }
+
+    /// All nodes start unreachable, except each `(source, offset)` at its offset (the smallest one
+    /// if a source is listed twice).
+    pub fn with_sources(num_nodes: usize, sources: &[(usize, f64)]) -> Self {
+        let mut dist = vec![f64::INFINITY; num_nodes];
+        for &(source, offset) in sources {
+            dist[source] = dist[source].min(offset);
+        }
+        Self {
+            dist,
+            pred: vec![None; num_nodes],
+        }
+    }
+
+    /// Sets the distance estimate of `node_id` to `cost`. The back pointer only moves on a strict
+    /// improvement: BMSSP re-labels nodes on equal costs, and following those ties with zero-weight
+    /// edges could otherwise close a cycle in the tree.
+    #[inline(always)]
+    pub fn set(&mut self, node_id: usize, cost: f64, pred: Predecessor) {
+        if cost < self.dist[node_id] {
+            self.pred[node_id] = Some(pred);
+        }
+        self.dist[node_id] = cost;
+    }
+
+    /// Nodes on the shortest path from the root to `target` (inclusive), or None if unreachable.
+    pub fn path_to(&self, target: usize) -> Option<Vec<usize>> {
+        if !self.dist[target].is_finite() {
+            return None;
+        }
+        let mut path = vec![target];
+        let mut cur = target;
+        while let Some(Predecessor { node_id, .. }) = self.pred[cur] {
+            path.push(node_id);
+            cur = node_id;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Root of every reached node's branch of the tree (the node itself for a root), or None if
+    /// unreachable.
+    pub fn roots(&self) -> Vec<Option<usize>> {
+        let mut roots = vec![None; self.dist.len()];
+        let mut branch = Vec::new();
+        for node_id in 0..self.dist.len() {
+            if roots[node_id].is_some() || !self.dist[node_id].is_finite() {
+                continue;
+            }
+            // Walk up until a root or a node whose root is already known.
+            let mut cur = node_id;
+            let root = loop {
+                if let Some(root) = roots[cur] {
+                    break root;
+                }
+                branch.push(cur);
+                match self.pred[cur] {
+                    Some(Predecessor { node_id, .. }) => cur = node_id,
+                    None => break cur,
+                }
+            };
+            for n in branch.drain(..) {
+                roots[n] = Some(root);
+            }
+        }
+        roots
+    }
+
+    /// Edges on the shortest path to `target`, in order from the root, or None if unreachable.
+    pub fn edges_to(&self, target: usize) -> Option<Vec<Predecessor>> {
+        if !self.dist[target].is_finite() {
+            return None;
+        }
+        let mut edges = Vec::new();
+        let mut cur = target;
+        while let Some(pred) = self.pred[cur] {
+            edges.push(pred);
+            cur = pred.node_id;
+        }
+        edges.reverse();
+        Some(edges)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pred(node_id: usize, edge_idx: usize) -> Predecessor {
+        Predecessor { node_id, edge_idx }
+    }
+
+    #[test]
+    fn path_reconstruction() {
+        let mut tree = ShortestPathTree::new(4, 0);
+        tree.set(1, 2.0, pred(0, 0));
+        tree.set(2, 3.0, pred(1, 1));
+        // Equal cost doesn't move the back pointer.
+        tree.set(2, 3.0, pred(0, 1));
+        assert_eq!(tree.path_to(2), Some(vec![0, 1, 2]));
+        assert_eq!(tree.edges_to(2), Some(vec![pred(0, 0), pred(1, 1)]));
+        assert_eq!(tree.path_to(0), Some(vec![0]));
+        assert_eq!(tree.path_to(3), None);
+        assert_eq!(tree.roots(), vec![Some(0), Some(0), Some(0), None]);
+    }
+
+    #[test]
+    fn roots_of_a_forest() {
+        let mut tree = ShortestPathTree::with_sources(5, &[(0, 0.0), (3, 1.0), (3, 4.0), (4, 9.0)]);
+        assert_eq!(tree.dist[3], 1.0);
+        tree.set(1, 2.0, pred(0, 0));
+        tree.set(2, 2.5, pred(3, 1));
+        // Source 4 is cheaper to reach from source 3 than its own offset.
+        tree.set(4, 3.0, pred(2, 2));
+        assert_eq!(
+            tree.roots(),
+            vec![Some(0), Some(0), Some(3), Some(3), Some(3)]
+        );
+    }
+}
@@ -0,0 +1,195 @@
+/*
+Uniform lat/lon grid over node coordinates, for snapping a coordinate to the nearest graph node.
+
+Points are bucketed into square cells of `cell_deg` degrees. A nearest-neighbour query scans rings of
+cells around the query cell, and stops once the closest point found so far is nearer than anything
+the next ring could contain. A query far from the data would need many rings before reaching any
+point, so past about sqrt(points) rings it scans every point instead.
+*/
+
+use crate::geo::{self, EARTH_RADIUS_M};
+use fnv::FnvHashMap;
+use std::f64::consts::FRAC_PI_2;
+
+type Cell = (i64, i64);
+
+pub struct SpatialIndex {
+    cell_deg: f64,
+    cells: FnvHashMap<Cell, Vec<usize>>,
+    points: Vec<(usize, f64, f64)>,
+    // Bounding box of the occupied cells, so empty-area queries terminate.
+    min_cell: Cell,
+    max_cell: Cell,
+}
+
+impl SpatialIndex {
+    /// Builds the index over `(node_idx, lat, lon)` triples.
+    pub fn new(points: Vec<(usize, f64, f64)>, cell_deg: f64) -> Self {
+        let mut cells: FnvHashMap<Cell, Vec<usize>> = FnvHashMap::default();
+        let mut min_cell = (i64::MAX, i64::MAX);
+        let mut max_cell = (i64::MIN, i64::MIN);
+        for (i, &(_, lat, lon)) in points.iter().enumerate() {
+            let cell = Self::cell_of(cell_deg, lat, lon);
+            min_cell = (min_cell.0.min(cell.0), min_cell.1.min(cell.1));
+            max_cell = (max_cell.0.max(cell.0), max_cell.1.max(cell.1));
+            cells.entry(cell).or_default().push(i);
+        }
+        Self {
+            cell_deg,
+            cells,
+            points,
+            min_cell,
+            max_cell,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    fn cell_of(cell_deg: f64, lat: f64, lon: f64) -> Cell {
+        (
+            (lat / cell_deg).floor() as i64,
+            (lon / cell_deg).floor() as i64,
+        )
+    }
+
+    /// Returns the nearest node index and its haversine distance in meters.
+    pub fn nearest(&self, lat: f64, lon: f64) -> Option<(usize, f64)> {
+        if self.points.is_empty() {
+            return None;
+        }
+        let (ci, cj) = Self::cell_of(self.cell_deg, lat, lon);
+        // Furthest ring that can still contain a point.
+        let max_ring = [
+            ci - self.min_cell.0,
+            self.max_cell.0 - ci,
+            cj - self.min_cell.1,
+            self.max_cell.1 - cj,
+        ]
+        .into_iter()
+        .max()
+        .unwrap()
+        .max(0);
+
+        // Ring r scans 8r cells, so up to here the rings cost about as much as a full scan.
+        let max_scanned_ring = (self.points.len() as f64).sqrt().ceil() as i64;
+
+        let mut best: Option<(usize, f64)> = None;
+        for ring in 0..=max_ring {
+            if let Some((_, best_dist)) = best {
+                // Anything in this ring is at least (ring - 1) whole cells away along one axis.
+                // Longitude cells shrink towards the poles, so use the highest latitude in reach.
+                let gap = ((ring - 1) as f64 * self.cell_deg).to_radians();
+                let reach_lat = (lat.abs() + (ring + 1) as f64 * self.cell_deg).min(90.0);
+                let lat_bound = gap * EARTH_RADIUS_M;
+                let lon_bound = 2.0
+                    * EARTH_RADIUS_M
+                    * (reach_lat.to_radians().cos() * (gap / 2.0).min(FRAC_PI_2).sin()).asin();
+                if best_dist <= lat_bound.min(lon_bound) {
+                    break;
+                }
+            }
+            if ring > max_scanned_ring {
+                return self.nearest_brute_force(lat, lon);
+            }
+            // Only the border of the ring; the inside was scanned already.
+            if ring == 0 {
+                self.scan_cell((ci, cj), lat, lon, &mut best);
+                continue;
+            }
+            for j in (cj - ring)..=(cj + ring) {
+                self.scan_cell((ci - ring, j), lat, lon, &mut best);
+                self.scan_cell((ci + ring, j), lat, lon, &mut best);
+            }
+            for i in (ci - ring + 1)..=(ci + ring - 1) {
+                self.scan_cell((i, cj - ring), lat, lon, &mut best);
+                self.scan_cell((i, cj + ring), lat, lon, &mut best);
+            }
+        }
+        best
+    }
+
+    fn scan_cell(&self, cell: Cell, lat: f64, lon: f64, best: &mut Option<(usize, f64)>) {
+        if let Some(bucket) = self.cells.get(&cell) {
+            for &p in bucket {
+                self.consider(p, lat, lon, best);
+            }
+        }
+    }
+
+    fn nearest_brute_force(&self, lat: f64, lon: f64) -> Option<(usize, f64)> {
+        let mut best = None;
+        for p in 0..self.points.len() {
+            self.consider(p, lat, lon, &mut best);
+        }
+        best
+    }
+
+    fn consider(&self, p: usize, lat: f64, lon: f64, best: &mut Option<(usize, f64)>) {
+        let (node_idx, plat, plon) = self.points[p];
+        let d = geo::haversine_meters(lat, lon, plat, plon);
+        if best.is_none_or(|(_, best_dist)| d < best_dist) {
+            *best = Some((node_idx, d));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Rng;
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let mut rng = Rng(7);
+        let mut next = || rng.unit();
+        let points: Vec<(usize, f64, f64)> = (0..500)
+            .map(|i| (i * 10, 38.8 + next() * 0.2, -77.1 + next() * 0.2))
+            .collect();
+        let index = SpatialIndex::new(points.clone(), 0.01);
+        assert_eq!(index.len(), 500);
+
+        for _ in 0..200 {
+            // Include queries well outside the data.
+            let (lat, lon) = (38.6 + next() * 0.6, -77.3 + next() * 0.6);
+            let expected = points
+                .iter()
+                .map(|&(idx, plat, plon)| (idx, geo::haversine_meters(lat, lon, plat, plon)))
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                .unwrap();
+            assert_eq!(index.nearest(lat, lon), Some(expected));
+        }
+    }
+
+    #[test]
+    fn nearest_far_outside_the_data() {
+        let mut rng = Rng(11);
+        let mut next = || rng.unit();
+        let points: Vec<(usize, f64, f64)> = (0..500)
+            .map(|i| (i, 38.8 + next() * 0.2, -77.1 + next() * 0.2))
+            .collect();
+        let index = SpatialIndex::new(points.clone(), 0.01);
+
+        // Swapped lat/lon is about 11,000 rings from the data.
+        for (lat, lon) in [(-77.0, 38.9), (0.0, 0.0), (89.9, 179.9)] {
+            let expected = points
+                .iter()
+                .map(|&(idx, plat, plon)| (idx, geo::haversine_meters(lat, lon, plat, plon)))
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                .unwrap();
+            assert_eq!(index.nearest(lat, lon), Some(expected));
+        }
+    }
+
+    #[test]
+    fn empty_index() {
+        let index = SpatialIndex::new(Vec::new(), 0.01);
+        assert!(index.is_empty());
+        assert_eq!(index.nearest(38.9, -77.0), None);
+    }
+}
@@ -0,0 +1,52 @@
+// Shared by the tests: a deterministic random number generator and the random graphs built from it.
+
+use crate::graph::Graph;
+
+/// Deterministic LCG (Knuth's MMIX constants), so every run sees the same graphs.
+pub(crate) struct Rng(pub u64);
+
+impl Rng {
+    pub fn next(&mut self) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 33) as usize
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        self.next() % n
+    }
+
+    /// Uniform in [0, 1), from the top 53 bits.
+    pub fn unit(&mut self) -> f64 {
+        self.next();
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Fine-grained weight in [0.5, 100.5), so distinct paths practically never tie.
+    pub fn weight(&mut self) -> f64 {
+        self.below(1_000_000) as f64 / 10_000.0 + 0.5
+    }
+}
+
+/// `degree` edges out of each of `num_nodes` nodes, to uniformly random heads (self-loops and
+/// parallel edges included), with `Rng::weight` weights.
+pub(crate) fn random_edges(
+    rng: &mut Rng,
+    num_nodes: usize,
+    degree: usize,
+) -> Vec<(usize, usize, f64)> {
+    let mut edges = Vec::new();
+    for u in 0..num_nodes {
+        for _ in 0..degree {
+            edges.push((u, rng.below(num_nodes), rng.weight()));
+        }
+    }
+    edges
+}
+
+/// Sparse random digraph: `random_edges` from a generator seeded with `seed`.
+pub(crate) fn random_graph(num_nodes: usize, degree: usize, seed: u64) -> Graph {
+    Graph::from_edges(num_nodes, &random_edges(&mut Rng(seed), num_nodes, degree))
+}
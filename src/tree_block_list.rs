@@ -3,10 +3,16 @@ Block data structure proposed in https://arxiv.org/pdf/2504.17033v1, but uses a
 
 Parameterized by M, and an upper bound B over all values (assuming values are floats) in the block.
 Supported operations are Add (Insert), BatchPreprend, and Pull
+
+Entries are ordered by (cost, node id), so any number of nodes can share a cost.
 */
 
+// M and B follow the paper's notation.
+#![allow(non_snake_case)]
+
+use crate::block_list::BlockListOps;
 use hashbrown::HashMap;
-use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 
 pub type NodeId = usize;
 pub type Cost = f64;
@@ -60,7 +66,7 @@ impl Ord for OrderedCost {
 pub struct BlockList {
     M: usize,
     B: Cost,
-    tree_map: BTreeMap<OrderedCost, NodeId>,
+    tree_set: BTreeSet<(OrderedCost, NodeId)>,
     nodes_to_costs: HashMap<NodeId, OrderedCost>,
 }
 
@@ -69,17 +75,25 @@ impl BlockList {
         Self {
             M,
             B,
-            tree_map: BTreeMap::new(),
+            tree_set: BTreeSet::new(),
             nodes_to_costs: HashMap::new(),
         }
     }
 
+    /// Empties the list and sets new parameters, keeping the allocations for reuse.
+    pub fn reset(&mut self, M: usize, B: Cost) {
+        self.M = M;
+        self.B = B;
+        self.tree_set.clear();
+        self.nodes_to_costs.clear();
+    }
+
     pub fn len(&self) -> usize {
-        self.tree_map.len()
+        self.tree_set.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.tree_map.is_empty()
+        self.tree_set.is_empty()
     }
 
     pub fn insert(&mut self, node_id: NodeId, cost: f64) {
@@ -96,20 +110,21 @@ impl BlockList {
                 }
 
                 // Remove old entry from tree
-                self.tree_map.remove(&old_cost);
+                self.tree_set.remove(&(old_cost, node_id));
 
                 // Update to new cost
                 entry.insert(new_cost);
-                self.tree_map.insert(new_cost, node_id);
+                self.tree_set.insert((new_cost, node_id));
             }
             hashbrown::hash_map::Entry::Vacant(entry) => {
                 entry.insert(new_cost);
-                self.tree_map.insert(new_cost, node_id);
+                self.tree_set.insert((new_cost, node_id));
             }
         }
     }
 
-    pub fn batch_prepend(&mut self, mut nodes_to_prepend: Vec<(NodeId, f64)>) {
+    /// Inserts every element of `nodes_to_prepend`, leaving it empty (with its capacity).
+    pub fn batch_prepend(&mut self, nodes_to_prepend: &mut Vec<(NodeId, f64)>) {
         // Sort by node_id to handle duplicates efficiently
         nodes_to_prepend.sort_unstable_by_key(|(id, _)| *id);
 
@@ -128,30 +143,69 @@ impl BlockList {
         // Sort by cost for better BTree insertion pattern
         nodes_to_prepend.sort_unstable_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
 
-        for (node_id, cost) in nodes_to_prepend {
+        for (node_id, cost) in nodes_to_prepend.drain(..) {
             self.insert(node_id, cost);
         }
     }
 
     fn get_minimum_bound(&self) -> Cost {
-        self.tree_map
-            .first_key_value()
-            .map_or(self.B, |(cost, _)| cost.as_f64()) // Avoid the intermediate map
+        self.tree_set
+            .first()
+            .map_or(self.B, |(cost, _)| cost.as_f64())
     }
 
     pub fn pull(&mut self) -> PullResult {
-        // Pull M elements.
         let mut pulled_elements = Vec::with_capacity(self.M);
-        for _ in 0..self.M {
-            if let Some((cost, node_id)) = self.tree_map.pop_first() {
-                pulled_elements.push(node_id);
-                self.nodes_to_costs.remove(&node_id);
-            } else {
+        let bound = self.pull_into(&mut pulled_elements);
+        PullResult(pulled_elements, bound)
+    }
+
+    /// `pull` into a caller-owned buffer, which is cleared first. Returns the new lower bound of
+    /// the remaining elements.
+    pub fn pull_into(&mut self, pulled_elements: &mut Vec<NodeId>) -> Cost {
+        pulled_elements.clear();
+        // Pull M elements, then any left at the last one's cost: the bound has to be strictly
+        // above everything pulled, or the caller can't complete any of it.
+        let mut last_cost = None;
+        while let Some(&(cost, node_id)) = self.tree_set.first() {
+            if pulled_elements.len() >= self.M && last_cost != Some(cost) {
                 break;
             }
+            self.tree_set.pop_first();
+            pulled_elements.push(node_id);
+            self.nodes_to_costs.remove(&node_id);
+            last_cost = Some(cost);
         }
 
-        PullResult(pulled_elements, self.get_minimum_bound())
+        self.get_minimum_bound()
+    }
+}
+
+impl Default for BlockList {
+    fn default() -> Self {
+        Self::new(1, Cost::INFINITY)
+    }
+}
+
+impl BlockListOps for BlockList {
+    fn reset(&mut self, M: usize, B: Cost) {
+        BlockList::reset(self, M, B)
+    }
+
+    fn insert(&mut self, node_id: NodeId, cost: Cost) {
+        BlockList::insert(self, node_id, cost)
+    }
+
+    fn batch_prepend(&mut self, nodes: &mut Vec<(NodeId, Cost)>) {
+        BlockList::batch_prepend(self, nodes)
+    }
+
+    fn pull(&mut self, pulled: &mut Vec<NodeId>) -> Cost {
+        self.pull_into(pulled)
+    }
+
+    fn is_empty(&self) -> bool {
+        BlockList::is_empty(self)
     }
 }
 
@@ -172,4 +226,51 @@ mod tests {
         assert_eq!(nodes, vec![4, 3]);
         assert_eq!(upper_bound, 7.5);
     }
+
+    #[test]
+    fn equal_costs_are_all_pulled() {
+        let mut block_list = BlockList::new(4, 100.0);
+        // 50 nodes at 5.0 and 50 at 3.0, interleaved, plus updates onto and off those costs.
+        for node_id in 0..100 {
+            block_list.insert(node_id, if node_id % 2 == 0 { 5.0 } else { 3.0 });
+        }
+        block_list.insert(100, 9.0);
+        block_list.insert(100, 5.0);
+        block_list.insert(0, 1.0);
+        block_list.batch_prepend(&mut vec![(101, 3.0), (2, 3.0), (101, 3.0)]);
+        assert_eq!(block_list.len(), 102);
+
+        let mut pulled = Vec::new();
+        let mut bounds = Vec::new();
+        while !block_list.is_empty() {
+            let PullResult(nodes, bound) = block_list.pull();
+            assert!(!nodes.is_empty());
+            pulled.push(nodes);
+            bounds.push(bound);
+        }
+        // M = 4, but a pull never splits nodes that share a cost, so the bound is always strictly
+        // above what it returned.
+        assert_eq!(pulled.len(), 2);
+        assert_eq!(pulled[0][0], 0);
+        assert_eq!((pulled[0].len(), bounds[0]), (1 + 52, 5.0));
+        assert_eq!((pulled[1].len(), bounds[1]), (49, 100.0));
+        let mut all: Vec<NodeId> = pulled.concat();
+        all.sort_unstable();
+        assert_eq!(all, (0..102).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn update_keeps_nodes_sharing_the_old_cost() {
+        let mut block_list = BlockList::new(1, 100.0);
+        block_list.insert(7, 5.0);
+        block_list.insert(8, 5.0);
+        block_list.insert(9, 5.0);
+        // Moving 8 off 5.0 must not take 7 or 9 with it.
+        block_list.insert(8, 2.0);
+        assert_eq!(block_list.len(), 3);
+        let PullResult(nodes, upper_bound) = block_list.pull();
+        assert_eq!((nodes, upper_bound), (vec![8], 5.0));
+        let PullResult(nodes, upper_bound) = block_list.pull();
+        assert_eq!((nodes, upper_bound), (vec![7, 9], 100.0));
+    }
 }
@@ -0,0 +1,151 @@
+// Sweeps BMSSP's parameters on a graph: checks each candidate against Dijkstra on a sample of
+// sources, then times the ones that agree and ranks them.
+
+use crate::algorithm::{Bmssp, ShortestPathAlgorithm};
+use crate::bmssp::BmsspParams;
+use crate::dijkstra;
+use crate::graph::Graph;
+use crate::query_context::QueryContext;
+use crate::verify::Comparison;
+use std::time::Instant;
+
+/// One candidate's timing.
+#[derive(Clone, Debug)]
+pub struct Trial {
+    pub params: BmsspParams,
+    /// Mean query latency over the sources.
+    pub mean_millis: f64,
+}
+
+/// What a sweep found.
+#[derive(Clone, Debug, Default)]
+pub struct Sweep {
+    /// The candidates that matched Dijkstra from every source, fastest first.
+    pub trials: Vec<Trial>,
+    /// The candidates whose distances differed from Dijkstra's from some source.
+    pub wrong: Vec<BmsspParams>,
+}
+
+/// Fewest levels from which a search with `k` and `t` settles `num_nodes` nodes (k 2^(lt) >= N).
+fn fewest_levels(num_nodes: usize, k: usize, t: usize) -> Option<usize> {
+    (0..=64).find(|&l| BmsspParams { k, t, l }.check(num_nodes).is_ok())
+}
+
+/// Candidates around the paper's values for a graph of `num_nodes` nodes: k in 1, 2, 4, ..., 32
+/// and the paper's k, t from 1 to twice the paper's t (at least 4), each with the fewest levels that
+/// cover the graph and with the paper's l = ceil(log N / t). The paper's own choice comes first.
+pub fn candidates(num_nodes: usize) -> Vec<BmsspParams> {
+    let paper = BmsspParams::for_graph(num_nodes);
+    let mut ks = vec![1, 2, 4, 8, 16, 32, paper.k];
+    ks.sort_unstable();
+    ks.dedup();
+    let mut candidates = vec![paper];
+    for t in 1..=(2 * paper.t).max(4) {
+        let paper_l = BmsspParams::with_overrides(num_nodes, None, Some(t), None)
+            .map(|params| params.l)
+            .ok();
+        for &k in &ks {
+            for l in [fewest_levels(num_nodes, k, t), paper_l]
+                .into_iter()
+                .flatten()
+            {
+                let params = BmsspParams { k, t, l };
+                if params.check(num_nodes).is_ok() && !candidates.contains(&params) {
+                    candidates.push(params);
+                }
+            }
+        }
+    }
+    candidates
+}
+
+/// Times `bmssp` with each of `candidates` from every one of `sources`, reusing one QueryContext.
+/// Candidates that can't cover the graph are skipped. Each candidate first runs untimed from every
+/// source and is compared with Dijkstra; that drops the ones that get a distance wrong, and warms
+/// the context up, so the first candidate isn't timed against a cold one.
+pub fn sweep(graph: &Graph, bmssp: Bmssp, sources: &[usize], candidates: &[BmsspParams]) -> Sweep {
+    let num_nodes = graph.num_nodes();
+    let mut configs: Vec<Bmssp> = candidates
+        .iter()
+        .filter_map(|&params| bmssp.with_params(params, num_nodes).ok())
+        .collect();
+    let mut ctx = QueryContext::new();
+    let mut wrong = Vec::new();
+    for &source in sources {
+        let expected = dijkstra::dijkstra_all(graph, source);
+        configs.retain(|config| {
+            let tree = config.tree_with(&mut ctx, graph, source, None);
+            let is_match = Comparison::new(&expected, &tree.dist).is_match();
+            if !is_match {
+                wrong.extend(config.params());
+            }
+            is_match
+        });
+    }
+    let mut trials: Vec<Trial> = configs
+        .iter()
+        .map(|config| {
+            let now = Instant::now();
+            for &source in sources {
+                config.tree_with(&mut ctx, graph, source, None);
+            }
+            Trial {
+                params: config.params().expect("set above"),
+                mean_millis: now.elapsed().as_secs_f64() * 1000.0 / sources.len().max(1) as f64,
+            }
+        })
+        .collect();
+    trials.sort_by(|a, b| a.mean_millis.total_cmp(&b.mean_millis));
+    Sweep { trials, wrong }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn candidates_cover_the_graph() {
+        for num_nodes in [1, 2, 100, 20_000, 1_000_000] {
+            let candidates = candidates(num_nodes);
+            assert_eq!(candidates[0], BmsspParams::for_graph(num_nodes));
+            for (i, params) in candidates.iter().enumerate() {
+                assert!(params.check(num_nodes).is_ok(), "{}", params);
+                assert!(!candidates[..i].contains(params));
+            }
+            assert!(candidates.iter().any(|params| params.k == 1));
+        }
+        // 20k nodes: log N = 14.3, so the paper picks k = 2, t = 5 and l = 3.
+        assert_eq!(
+            BmsspParams::for_graph(20_000),
+            BmsspParams { k: 2, t: 5, l: 3 }
+        );
+        // 4 2^(3 * 4) = 16384 falls short.
+        assert_eq!(fewest_levels(20_000, 4, 4), Some(4));
+        assert_eq!(fewest_levels(20_000, 1, 0), None);
+    }
+
+    #[test]
+    fn sweep_ranks_every_candidate() {
+        let graph = Graph::from_edges(
+            5,
+            &[
+                (0, 1, 1.0),
+                (1, 2, 1.0),
+                (2, 3, 1.0),
+                (0, 3, 5.0),
+                (3, 4, 1.0),
+            ],
+        );
+        let candidates = [
+            BmsspParams { k: 1, t: 1, l: 3 },
+            BmsspParams { k: 5, t: 0, l: 0 },
+            // Can't cover 5 nodes, so it's skipped.
+            BmsspParams { k: 1, t: 1, l: 1 },
+        ];
+        let Sweep { trials, wrong } = sweep(&graph, Bmssp::default(), &[0, 2, 4], &candidates);
+        assert!(wrong.is_empty());
+        assert_eq!(trials.len(), 2);
+        assert!(trials[0].mean_millis <= trials[1].mean_millis);
+        assert!(trials.iter().all(|trial| trial.params != candidates[2]));
+    }
+}
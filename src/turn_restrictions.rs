@@ -0,0 +1,312 @@
+// Turn restrictions from OSM `type=restriction` relations, modelled by splitting via nodes.
+//
+// Every via node v keeps its own index as the departure node, with all of v's outgoing edges and no
+// incoming ones, so searches can still start there. Each way arriving at v gets its own copy of v:
+// edges along that way into v land on the copy, and the copy only has the outgoing edges the
+// restrictions allow after arriving on that way. The distance to v is the minimum over v and its
+// copies (see OsmGraph::collapse).
+
+use crate::profile::Profile;
+use fnv::FnvHashMap;
+use osmpbfreader::{NodeId, OsmId, Relation, Tags, WayId};
+
+/// A restriction on turning from one way onto another at a node.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Restriction {
+    pub from: WayId,
+    pub via: NodeId,
+    pub to: WayId,
+    /// `only_*` restrictions forbid every other turn from `from` at `via`; `no_*` forbid this one.
+    pub only: bool,
+}
+
+/// How the restriction relations in an extract were handled.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RestrictionCounts {
+    /// Restrictions applied to the graph.
+    pub applied: usize,
+    /// Restrictions with a via way rather than a via node, which aren't supported.
+    pub via_way: usize,
+    /// Malformed restrictions, or ones whose members aren't routable ways meeting at the via node.
+    pub invalid: usize,
+}
+
+/// Outcome of reading one relation.
+pub enum ParsedRelation {
+    /// Not a restriction, or one that doesn't apply to the profile.
+    Ignored,
+    Restrictions(Vec<Restriction>),
+    ViaWay,
+    Invalid,
+}
+
+/// The tag keys holding the restriction for a profile, most specific first, and the `except`
+/// values that exempt it. Pedestrians aren't subject to turn restrictions.
+fn restriction_keys(
+    profile: Option<Profile>,
+) -> Option<(&'static [&'static str], &'static [&'static str])> {
+    match profile {
+        None | Some(Profile::Car) => Some((
+            &[
+                "restriction:motorcar",
+                "restriction:motor_vehicle",
+                "restriction",
+            ],
+            &["motorcar", "motor_vehicle"],
+        )),
+        Some(Profile::Bike) => Some((&["restriction:bicycle", "restriction"], &["bicycle"])),
+        Some(Profile::Foot) => None,
+    }
+}
+
+fn restriction_value(tags: &Tags, profile: Option<Profile>) -> Option<&str> {
+    let (keys, exempt) = restriction_keys(profile)?;
+    let exempted = tags
+        .get("except")
+        .map(|v| v.split(';').any(|mode| exempt.contains(&mode.trim())))
+        .unwrap_or(false);
+    if exempted {
+        return None;
+    }
+    keys.iter()
+        .find_map(|&key| tags.get(key))
+        .map(|v| v.as_str())
+}
+
+/// Reads a `type=restriction` relation. Several `from` (no_entry) or `to` (no_exit) members yield
+/// one restriction per pair.
+pub fn parse_relation(relation: &Relation, profile: Option<Profile>) -> ParsedRelation {
+    if relation.tags.get("type").map(|v| v.as_str()) != Some("restriction") {
+        return ParsedRelation::Ignored;
+    }
+    let Some(value) = restriction_value(&relation.tags, profile) else {
+        return ParsedRelation::Ignored;
+    };
+    let only = if value.starts_with("only_") {
+        true
+    } else if value.starts_with("no_") {
+        false
+    } else {
+        return ParsedRelation::Invalid;
+    };
+
+    let mut from = Vec::new();
+    let mut to = Vec::new();
+    let mut via = Vec::new();
+    for member in &relation.refs {
+        match (member.role.as_str(), member.member) {
+            ("from", OsmId::Way(way)) => from.push(way),
+            ("to", OsmId::Way(way)) => to.push(way),
+            ("via", OsmId::Node(node)) => via.push(node),
+            ("via", OsmId::Way(_)) => return ParsedRelation::ViaWay,
+            _ => {}
+        }
+    }
+    if via.len() != 1 || from.is_empty() || to.is_empty() {
+        return ParsedRelation::Invalid;
+    }
+    let via = via[0];
+    let restrictions = from
+        .iter()
+        .flat_map(|&f| {
+            to.iter().map(move |&t| Restriction {
+                from: f,
+                via,
+                to: t,
+                only,
+            })
+        })
+        .collect();
+    ParsedRelation::Restrictions(restrictions)
+}
+
+/// Turn rules at one via node, keyed by the way arrived on.
+#[derive(Default)]
+struct ViaRules {
+    forbidden: Vec<(WayId, WayId)>,
+    only: Vec<(WayId, WayId)>,
+}
+
+impl ViaRules {
+    fn allows(&self, from: WayId, to: WayId) -> bool {
+        let mut only = self.only.iter().filter(|&&(f, _)| f == from).peekable();
+        if only.peek().is_some() {
+            return only.any(|&(_, t)| t == to);
+        }
+        !self.forbidden.contains(&(from, to))
+    }
+}
+
+/// Rewrites `edges` (`(from, to, weight, way)` over nodes 0..num_nodes) so the restrictions, given
+/// with their via node as a graph index, hold. Returns the new edge list and, for every added node
+/// (indices num_nodes..), the index of the node it is a copy of.
+pub fn split_via_nodes(
+    num_nodes: usize,
+    edges: &[(usize, usize, f64, WayId)],
+    restrictions: &[(WayId, usize, WayId, bool)],
+) -> (Vec<(usize, usize, f64)>, Vec<usize>) {
+    let mut rules: FnvHashMap<usize, ViaRules> = FnvHashMap::default();
+    for &(from, via, to, only) in restrictions {
+        let via_rules = rules.entry(via).or_default();
+        if only {
+            via_rules.only.push((from, to));
+        } else {
+            via_rules.forbidden.push((from, to));
+        }
+    }
+
+    // One arrival copy per (via node, way arriving on it).
+    let mut copy_of = Vec::new();
+    let mut arrival: FnvHashMap<(usize, WayId), usize> = FnvHashMap::default();
+    let mut arrivals_at: FnvHashMap<usize, Vec<(WayId, usize)>> = FnvHashMap::default();
+    for &(_, v, _, way) in edges {
+        if rules.contains_key(&v) && !arrival.contains_key(&(v, way)) {
+            let copy = num_nodes + copy_of.len();
+            copy_of.push(v);
+            arrival.insert((v, way), copy);
+            arrivals_at.entry(v).or_default().push((way, copy));
+        }
+    }
+
+    let mut split_edges = Vec::with_capacity(edges.len());
+    for &(u, v, w, way) in edges {
+        let head = arrival.get(&(v, way)).copied().unwrap_or(v);
+        split_edges.push((u, head, w));
+        if let (Some(via_rules), Some(copies)) = (rules.get(&u), arrivals_at.get(&u)) {
+            for &(from, copy) in copies {
+                if via_rules.allows(from, way) {
+                    split_edges.push((copy, head, w));
+                }
+            }
+        }
+    }
+    (split_edges, copy_of)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dijkstra::dijkstra_all;
+    use crate::graph::Graph;
+
+    // A crossroads at node 0 with arms 1 (way 1, from the south), 2 (way 2, east), 3 (way 3,
+    // north) and 4 (way 4, west), each 1.0 long, plus a 3.0 detour from 1 to 2 along way 5.
+    fn crossroads() -> Vec<(usize, usize, f64, WayId)> {
+        let mut edges = Vec::new();
+        for arm in 1..=4 {
+            edges.push((arm, 0, 1.0, WayId(arm as i64)));
+            edges.push((0, arm, 1.0, WayId(arm as i64)));
+        }
+        edges.push((1, 2, 3.0, WayId(5)));
+        edges
+    }
+
+    fn distances(
+        edges: &[(usize, usize, f64, WayId)],
+        restrictions: &[(WayId, usize, WayId, bool)],
+        source: usize,
+    ) -> Vec<f64> {
+        let (split, copy_of) = split_via_nodes(5, edges, restrictions);
+        let mut dist = dijkstra_all(&Graph::from_edges(5 + copy_of.len(), &split), source);
+        for (i, &original) in copy_of.iter().enumerate() {
+            dist[original] = dist[original].min(dist[5 + i]);
+        }
+        dist.truncate(5);
+        dist
+    }
+
+    #[test]
+    fn no_turn_forces_detour() {
+        let edges = crossroads();
+        assert_eq!(distances(&edges, &[], 1), vec![1.0, 0.0, 2.0, 2.0, 2.0]);
+
+        // Turning onto way 2 now takes the detour.
+        let no_right = [(WayId(1), 0, WayId(2), false)];
+        assert_eq!(
+            distances(&edges, &no_right, 1),
+            vec![1.0, 0.0, 3.0, 2.0, 2.0]
+        );
+        // Starting at the via node itself, every turn is still open.
+        assert_eq!(
+            distances(&edges, &no_right, 0),
+            vec![0.0, 1.0, 1.0, 1.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn only_turn_forbids_the_rest() {
+        let edges = crossroads();
+        let only_straight_on = [(WayId(1), 0, WayId(3), true)];
+        // Node 4 is only reached by going straight on to 3 and turning back.
+        assert_eq!(
+            distances(&edges, &only_straight_on, 1),
+            vec![1.0, 0.0, 3.0, 2.0, 4.0]
+        );
+    }
+
+    #[test]
+    fn parses_relations() {
+        let relation = |tags: &[(&str, &str)], refs: Vec<(&str, OsmId)>| Relation {
+            id: osmpbfreader::RelationId(1),
+            tags: tags.iter().map(|&(k, v)| (k.into(), v.into())).collect(),
+            refs: refs
+                .into_iter()
+                .map(|(role, member)| osmpbfreader::Ref {
+                    member,
+                    role: role.into(),
+                })
+                .collect(),
+        };
+        let members = || {
+            vec![
+                ("from", OsmId::Way(WayId(1))),
+                ("via", OsmId::Node(NodeId(7))),
+                ("to", OsmId::Way(WayId(2))),
+            ]
+        };
+        let no_left = relation(
+            &[("type", "restriction"), ("restriction", "no_left_turn")],
+            members(),
+        );
+        let expected = Restriction {
+            from: WayId(1),
+            via: NodeId(7),
+            to: WayId(2),
+            only: false,
+        };
+        assert!(matches!(
+            parse_relation(&no_left, Some(Profile::Car)),
+            ParsedRelation::Restrictions(r) if r == vec![expected]
+        ));
+        assert!(matches!(
+            parse_relation(&no_left, Some(Profile::Foot)),
+            ParsedRelation::Ignored
+        ));
+
+        let except_bikes = relation(
+            &[
+                ("type", "restriction"),
+                ("restriction", "no_left_turn"),
+                ("except", "bicycle"),
+            ],
+            members(),
+        );
+        assert!(matches!(
+            parse_relation(&except_bikes, Some(Profile::Bike)),
+            ParsedRelation::Ignored
+        ));
+
+        let via_way = relation(
+            &[("type", "restriction"), ("restriction", "no_u_turn")],
+            vec![
+                ("from", OsmId::Way(WayId(1))),
+                ("via", OsmId::Way(WayId(3))),
+                ("to", OsmId::Way(WayId(2))),
+            ],
+        );
+        assert!(matches!(
+            parse_relation(&via_way, None),
+            ParsedRelation::ViaWay
+        ));
+    }
+}
@@ -0,0 +1,115 @@
+// Cross-checks the distances one algorithm found against a reference run (Dijkstra's, for the
+// binaries' --verify).
+
+use std::fmt;
+
+/// Largest relative difference two distances may have and still count as equal. Algorithms add up
+/// the same path in different orders, so exact equality is too strict.
+pub const TOLERANCE: f64 = 1e-9;
+
+/// How many mismatched nodes a comparison keeps as examples.
+const MAX_EXAMPLES: usize = 5;
+
+/// Outcome of comparing two distance vectors node by node.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Comparison {
+    pub num_nodes: usize,
+    pub mismatches: usize,
+    /// Over all nodes; infinite when one side reached a node the other didn't.
+    pub max_abs_error: f64,
+    pub max_rel_error: f64,
+    /// The first mismatched nodes, as `(node, expected, actual)`.
+    pub examples: Vec<(usize, f64, f64)>,
+}
+
+impl Comparison {
+    pub fn new(expected: &[f64], actual: &[f64]) -> Self {
+        assert_eq!(
+            expected.len(),
+            actual.len(),
+            "distance vectors differ in length"
+        );
+        let mut comparison = Self {
+            num_nodes: expected.len(),
+            ..Self::default()
+        };
+        for (node_id, (&e, &a)) in expected.iter().zip(actual).enumerate() {
+            let (abs_error, rel_error) = if e == a {
+                (0.0, 0.0)
+            } else if e.is_infinite() || a.is_infinite() {
+                (f64::INFINITY, f64::INFINITY)
+            } else {
+                let abs_error = (e - a).abs();
+                (abs_error, abs_error / e.abs().max(a.abs()))
+            };
+            comparison.max_abs_error = comparison.max_abs_error.max(abs_error);
+            comparison.max_rel_error = comparison.max_rel_error.max(rel_error);
+            // NaN never counts as a match.
+            if rel_error > TOLERANCE || rel_error.is_nan() {
+                comparison.mismatches += 1;
+                if comparison.examples.len() < MAX_EXAMPLES {
+                    comparison.examples.push((node_id, e, a));
+                }
+            }
+        }
+        comparison
+    }
+
+    pub fn is_match(&self) -> bool {
+        self.mismatches == 0
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} nodes differ (max abs error {:.6e}, max rel error {:.3e})",
+            self.mismatches, self.num_nodes, self.max_abs_error, self.max_rel_error
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_mismatches_and_errors() {
+        let expected = [0.0, 1.0, 2.0, f64::INFINITY, 1e6, 4.0, 5.0, 6.0, 7.0, 8.0];
+        let same = Comparison::new(&expected, &expected);
+        assert!(same.is_match());
+        assert_eq!(same.num_nodes, 10);
+        assert_eq!((same.max_abs_error, same.max_rel_error), (0.0, 0.0));
+
+        // Rounding noise is tolerated but still shows up in the error.
+        let mut actual = expected;
+        actual[4] = 1e6 + 1e-6;
+        let close = Comparison::new(&expected, &actual);
+        assert!(close.is_match());
+        assert!(close.max_abs_error > 0.0 && close.max_rel_error <= TOLERANCE);
+
+        actual[1] = 1.5;
+        let finite = Comparison::new(&expected, &actual);
+        assert_eq!(finite.mismatches, 1);
+        assert_eq!(finite.max_abs_error, 0.5);
+        assert!((finite.max_rel_error - 1.0 / 3.0).abs() < 1e-12);
+
+        actual[3] = 9.0;
+        for d in &mut actual[5..] {
+            *d += 1.0;
+        }
+        let differ = Comparison::new(&expected, &actual);
+        assert!(!differ.is_match());
+        assert_eq!(differ.mismatches, 7);
+        assert_eq!(differ.max_abs_error, f64::INFINITY);
+        assert_eq!(differ.max_rel_error, f64::INFINITY);
+        assert_eq!(differ.examples.len(), 5);
+        assert_eq!(differ.examples[0], (1, 1.0, 1.5));
+        assert_eq!(differ.examples[1], (3, f64::INFINITY, 9.0));
+        assert_eq!(
+            finite.to_string(),
+            "1 of 10 nodes differ (max abs error 5.000000e-1, max rel error 3.333e-1)"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+// Edge weightings for OSM graphs: segment length in meters, or travel time in seconds from the
+// way's `maxspeed` tag (falling back to a default speed per `highway` class).
+
+use osmpbfreader::Tags;
+use std::fmt;
+use std::str::FromStr;
+
+/// Speed used when neither `maxspeed` nor the `highway` class gives one.
+const FALLBACK_SPEED_KMH: f64 = 30.0;
+
+const KMH_PER_MPH: f64 = 1.609344;
+const KMH_PER_KNOT: f64 = 1.852;
+
+/// Weighting selector for the command line (`--weight distance|time`).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Weighting {
+    /// Haversine segment length in meters.
+    #[default]
+    Distance,
+    /// Travel time in seconds at the way's speed.
+    Time,
+}
+
+impl Weighting {
+    /// Weight of a segment of `meters` travelled at `speed_kmh`.
+    pub fn edge_weight(self, meters: f64, speed_kmh: f64) -> f64 {
+        match self {
+            Weighting::Distance => meters,
+            Weighting::Time => meters / (speed_kmh / 3.6),
+        }
+    }
+
+    /// Unit of the resulting costs, for output headers.
+    pub fn unit(self) -> &'static str {
+        match self {
+            Weighting::Distance => "m",
+            Weighting::Time => "s",
+        }
+    }
+
+    /// Output CSV column name for a cost.
+    pub fn column(self) -> &'static str {
+        match self {
+            Weighting::Distance => "distance_m",
+            Weighting::Time => "time_s",
+        }
+    }
+}
+
+impl FromStr for Weighting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "distance" => Ok(Weighting::Distance),
+            "time" => Ok(Weighting::Time),
+            _ => Err(format!(
+                "Weighting not found for input string: {}, possible options are: (\"distance\", \"time\")",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Weighting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Weighting::Distance => "distance",
+            Weighting::Time => "time",
+        })
+    }
+}
+
+/// Speed along a way in km/h: the parsed `maxspeed` if there is a usable one, else the default for
+/// its `highway` class.
+pub fn speed_kmh(tags: &Tags) -> f64 {
+    let highway = tags.get("highway").map(|v| v.as_str()).unwrap_or("");
+    tags.get("maxspeed")
+        .and_then(|v| parse_maxspeed(v))
+        .unwrap_or_else(|| default_speed_kmh(highway))
+}
+
+/// Typical speed for a `highway` class, in km/h.
+pub fn default_speed_kmh(highway: &str) -> f64 {
+    match highway {
+        "motorway" => 110.0,
+        "trunk" => 90.0,
+        "primary" => 70.0,
+        "secondary" => 60.0,
+        "tertiary" => 50.0,
+        "motorway_link" => 60.0,
+        "trunk_link" => 50.0,
+        "primary_link" => 45.0,
+        "secondary_link" => 40.0,
+        "tertiary_link" => 35.0,
+        "unclassified" => 40.0,
+        "residential" | "road" => 30.0,
+        "service" | "track" => 15.0,
+        "living_street" => 10.0,
+        "footway" | "path" | "pedestrian" | "steps" | "cycleway" | "bridleway" => 5.0,
+        _ => FALLBACK_SPEED_KMH,
+    }
+}
+
+/// Parses a `maxspeed` value into km/h. Handles plain numbers (km/h), "mph", "knots", "km/h",
+/// "walk" and implicit zone values like "DE:urban". Returns None for "none", "signals" and other
+/// values without a fixed limit, so the caller falls back to the class default.
+pub fn parse_maxspeed(value: &str) -> Option<f64> {
+    // Multiple values ("50;30") are per-lane or conditional; take the first.
+    let value = value.split(';').next()?.trim();
+    match value {
+        "walk" => return Some(5.0),
+        "none" | "signals" | "variable" | "unknown" => return None,
+        _ => {}
+    }
+    if let Some((_, zone)) = value.split_once(':') {
+        return match zone {
+            "urban" => Some(50.0),
+            "rural" => Some(90.0),
+            "zone30" => Some(30.0),
+            "zone20" => Some(20.0),
+            "living_street" => Some(7.0),
+            _ => None,
+        };
+    }
+
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let speed: f64 = value[..split].parse().ok()?;
+    let kmh = match value[split..].trim() {
+        "" | "km/h" | "kmh" | "kph" => speed,
+        "mph" => speed * KMH_PER_MPH,
+        "knots" => speed * KMH_PER_KNOT,
+        _ => return None,
+    };
+    (kmh > 0.0).then_some(kmh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maxspeed_variants() {
+        assert_eq!(parse_maxspeed("50"), Some(50.0));
+        assert_eq!(parse_maxspeed("50 km/h"), Some(50.0));
+        assert!((parse_maxspeed("30 mph").unwrap() - 48.28032).abs() < 1e-9);
+        assert!((parse_maxspeed("10 knots").unwrap() - 18.52).abs() < 1e-9);
+        assert_eq!(parse_maxspeed("60;40"), Some(60.0));
+        assert_eq!(parse_maxspeed("DE:urban"), Some(50.0));
+        assert_eq!(parse_maxspeed("DE:rural"), Some(90.0));
+        assert_eq!(parse_maxspeed("walk"), Some(5.0));
+        assert_eq!(parse_maxspeed("none"), None);
+        assert_eq!(parse_maxspeed("DE:motorway"), None);
+        assert_eq!(parse_maxspeed("0"), None);
+        assert_eq!(parse_maxspeed("fast"), None);
+    }
+
+    #[test]
+    fn time_weights() {
+        let mut tags = Tags::new();
+        tags.insert("highway".into(), "motorway".into());
+        assert_eq!(
+            Weighting::Distance.edge_weight(1000.0, speed_kmh(&tags)),
+            1000.0
+        );
+        // No maxspeed: the motorway default (110 km/h).
+        assert!((Weighting::Time.edge_weight(1100.0, speed_kmh(&tags)) - 36.0).abs() < 1e-9);
+        // maxspeed=none also falls back to the class default.
+        tags.insert("maxspeed".into(), "none".into());
+        assert!((Weighting::Time.edge_weight(1100.0, speed_kmh(&tags)) - 36.0).abs() < 1e-9);
+        tags.insert("maxspeed".into(), "36".into());
+        assert!((Weighting::Time.edge_weight(100.0, speed_kmh(&tags)) - 10.0).abs() < 1e-9);
+    }
+}